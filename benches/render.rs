@@ -0,0 +1,76 @@
+//! Render a long asciicast with the cast2svg binary.
+//!
+//! The asciicast is generated, simulating a 20 minute shell session that
+//! types commands and scrolls colored output.
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::{fs, path::PathBuf, process::Command};
+
+/// Duration of the generated asciicast, in seconds.
+const DURATION: f64 = 20.0 * 60.0;
+
+fn long_cast() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    path.push("long.cast");
+
+    let mut cast: String = String::from(
+        r#"{"version": 2, "width": 80, "height": 24, "env": {"TERM": "xterm-256color"}}"#,
+    );
+    cast.push('\n');
+    let mut time: f64 = 0.0;
+    let mut command_num: usize = 0;
+    while time < DURATION {
+        time += 0.5;
+        cast.push_str(&format!(
+            "[{:.3}, \"o\", \"\\u001b[32m$\\u001b[0m \"]\n",
+            time
+        ));
+        let command: String = format!("ls -l directory-{}", command_num);
+        for ch in command.chars() {
+            time += 0.12;
+            cast.push_str(&format!("[{:.3}, \"o\", \"{}\"]\n", time, ch));
+        }
+        time += 0.2;
+        cast.push_str(&format!("[{:.3}, \"o\", \"\\r\\n\"]\n", time));
+        for line in 0..(command_num % 16) {
+            time += 0.05;
+            cast.push_str(&format!(
+                "[{:.3}, \"o\", \"-rw-r--r-- 1 user user {:>6} \\u001b[3{}mfile-{}-{}\\u001b[0m\\r\\n\"]\n",
+                time,
+                (command_num * 7919 + line * 104729) % 1_000_000,
+                1 + line % 6,
+                command_num,
+                line,
+            ));
+        }
+        command_num += 1;
+    }
+    fs::write(&path, cast).unwrap();
+    path
+}
+
+fn render(c: &mut Criterion) {
+    let input: PathBuf = long_cast();
+    let mut output = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    output.push("long.svg");
+
+    let mut group = c.benchmark_group("render");
+    group.sample_size(10);
+    for (name, args) in [("long", vec![]), ("long_jobs", vec!["--jobs", "0"])] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let status = Command::new(env!("CARGO_BIN_EXE_cast2svg"))
+                    .arg(&input)
+                    .arg("--output")
+                    .arg(&output)
+                    .args(&args)
+                    .status()
+                    .unwrap();
+                assert!(status.success());
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
@@ -0,0 +1,242 @@
+//! Animation backends.
+use crate::timing::{Loop, Timeline};
+use crate::trimmed;
+use std::io::{self, Write};
+use xmlwriter::XmlWriter;
+
+/// Animation backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum Animation {
+    /// CSS `@keyframes`, referenced from `style` attributes.
+    Css,
+    /// SMIL animation elements.
+    ///
+    /// Each frame is shown by animating its visibility, the strip frame
+    /// layout is replaced by the stacked frame layout.
+    Smil,
+}
+
+/// Writes animations with the selected backend.
+pub struct Animator {
+    backend: Animation,
+    timeline: Timeline,
+    loop_count: Loop,
+    /// CSS rules to write into the `<style>` element.
+    css: Vec<u8>,
+    /// Whether CSS rules are written later instead of kept in `css`.
+    defer_css: bool,
+    /// Number of visibility animations, used for unique `@keyframes` names.
+    num_visibility: usize,
+}
+
+impl Animator {
+    pub fn new(backend: Animation, timeline: Timeline, loop_count: Loop) -> Animator {
+        Animator {
+            backend,
+            timeline,
+            loop_count,
+            css: Vec::new(),
+            defer_css: false,
+            num_visibility: 0,
+        }
+    }
+
+    /// Don't keep CSS rules, they are written later with
+    /// [`Animator::write_translate_x_css`] and
+    /// [`Animator::write_visibility_css`].
+    ///
+    /// This keeps memory from growing with the number of frames when
+    /// streaming.
+    pub fn defer_css(&mut self) {
+        self.defer_css = true;
+    }
+
+    /// CSS rules for the `<style>` element.
+    pub fn css(&self) -> &str {
+        std::str::from_utf8(&self.css).expect("CSS rules are UTF-8")
+    }
+
+    /// Animate the x translation of the current element.
+    ///
+    /// `steps` are pairs of the start time, and the translation in user units.
+    /// The translation before the first step is zero.
+    /// The last step is held until the end of the animation.
+    ///
+    /// This is only supported by the CSS backend, SMIL animates the visibility
+    /// of each frame instead of sliding a strip of frames.
+    ///
+    /// This must be called after all attributes have been written, and before
+    /// any child elements are written.
+    pub fn translate_x<I>(&mut self, svg: &mut XmlWriter, steps: I)
+    where
+        I: IntoIterator<Item = (f64, f64)>,
+    {
+        match self.backend {
+            Animation::Css => {
+                self.write_css_properties(svg, "l");
+                if !self.defer_css {
+                    let mut css: Vec<u8> = std::mem::take(&mut self.css);
+                    self.write_translate_x_css(&mut css, steps)
+                        .expect("writing to a Vec cannot fail");
+                    self.css = css;
+                }
+            }
+            Animation::Smil => unreachable!("SMIL animates the visibility of each frame"),
+        }
+    }
+
+    /// Write the CSS rule of [`Animator::translate_x`], a step at a time.
+    pub fn write_translate_x_css<I>(&self, output: &mut dyn Write, steps: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = (f64, f64)>,
+    {
+        write!(output, "@keyframes l{{0%{{transform:translateX(0)}}")?;
+        let mut last: Option<(f64, f64)> = None;
+        for (time, x) in steps {
+            // e.g. "0.62%{transform:translateX(-1280px)}"
+            write!(
+                output,
+                "{}%{{transform:translateX({}px)}}",
+                trimmed(self.timeline.percent(time), self.timeline.precision),
+                trimmed(x, 2),
+            )?;
+            last = Some((time, x));
+        }
+        if let Some((time, x)) = last {
+            if trimmed(self.timeline.percent(time), self.timeline.precision) != "100" {
+                // hold the last step until the end of the animation
+                write!(output, "100%{{transform:translateX({}px)}}", trimmed(x, 2))?;
+            }
+        }
+        write!(output, "}}")
+    }
+
+    /// Animate the visibility of the current element.
+    ///
+    /// `intervals` are pairs of start and end times that the element is
+    /// visible for, in ascending order.
+    /// An end of `None` keeps the element visible until the end of the
+    /// animation.
+    ///
+    /// This must be called after all attributes have been written, and before
+    /// any child elements are written.
+    pub fn visibility(&mut self, svg: &mut XmlWriter, intervals: &[(f64, Option<f64>)]) {
+        match self.backend {
+            Animation::Css => {
+                let index: usize = self.num_visibility;
+                self.num_visibility += 1;
+                self.write_css_properties(svg, &format!("v{}", index));
+                if !self.defer_css {
+                    let mut css: Vec<u8> = std::mem::take(&mut self.css);
+                    self.write_visibility_css(&mut css, index, intervals)
+                        .expect("writing to a Vec cannot fail");
+                    self.css = css;
+                }
+            }
+            Animation::Smil => {
+                let states: Vec<(String, bool)> = self.visibility_states(intervals);
+                let key_times: Vec<String> = states
+                    .iter()
+                    .map(|(pct, _)| {
+                        trimmed(
+                            pct.parse::<f64>().unwrap() / 100.0,
+                            self.timeline.precision + 2,
+                        )
+                    })
+                    .collect();
+                let values: Vec<&str> = states.iter().map(|(_, v)| visibility(*v)).collect();
+                svg.start_element("animate");
+                svg.write_attribute("attributeName", "visibility");
+                self.write_smil_attributes(svg, &key_times.join(";"), &values.join(";"));
+                svg.end_element(); // animate
+            }
+        }
+    }
+
+    /// Write the CSS rule of the `index`th call to [`Animator::visibility`].
+    ///
+    /// Nothing is written for the SMIL backend.
+    pub fn write_visibility_css(
+        &self,
+        output: &mut dyn Write,
+        index: usize,
+        intervals: &[(f64, Option<f64>)],
+    ) -> io::Result<()> {
+        if self.backend != Animation::Css {
+            return Ok(());
+        }
+        write!(output, "@keyframes v{}{{", index)?;
+        for (pct, visible) in self.visibility_states(intervals).iter() {
+            write!(output, "{}%{{visibility:{}}}", pct, visibility(*visible))?;
+        }
+        write!(output, "}}")
+    }
+
+    /// Visibility states at each percentage of the animation.
+    fn visibility_states(&self, intervals: &[(f64, Option<f64>)]) -> Vec<(String, bool)> {
+        let mut states: Vec<(String, bool)> = vec![(String::from("0"), false)];
+        for &(start, end) in intervals.iter() {
+            for (time, visible) in [(Some(start), true), (end, false)] {
+                let pct: String = match time {
+                    Some(time) => trimmed(self.timeline.percent(time), self.timeline.precision),
+                    None => break,
+                };
+                match states.last_mut() {
+                    Some(last) if last.0 == pct => last.1 = visible,
+                    _ => states.push((pct, visible)),
+                }
+            }
+        }
+        if states.last().unwrap().0 != "100" {
+            // hold the last state until the end of the animation
+            let last_visible: bool = states.last().unwrap().1;
+            states.push((String::from("100"), last_visible));
+        }
+        states
+    }
+
+    fn write_css_properties(&self, svg: &mut XmlWriter, name: &str) {
+        svg.write_attribute_fmt(
+            "style",
+            format_args!(
+                "animation-duration:{}s;\
+                animation-iteration-count:{};\
+                animation-name:{};\
+                animation-timing-function:steps(1,end){}",
+                trimmed(self.timeline.duration, 3),
+                self.loop_count,
+                name,
+                if self.loop_count == Loop::Infinite {
+                    ""
+                } else {
+                    ";animation-fill-mode:forwards"
+                }
+            ),
+        );
+    }
+
+    fn write_smil_attributes(&self, svg: &mut XmlWriter, key_times: &str, values: &str) {
+        svg.write_attribute("calcMode", "discrete");
+        svg.write_attribute_fmt(
+            "dur",
+            format_args!("{}s", trimmed(self.timeline.duration, 3)),
+        );
+        match self.loop_count {
+            Loop::Infinite => svg.write_attribute("repeatCount", "indefinite"),
+            Loop::Count(count) => {
+                svg.write_attribute("repeatCount", &count);
+                svg.write_attribute("fill", "freeze");
+            }
+        }
+        svg.write_attribute("keyTimes", key_times);
+        svg.write_attribute("values", values);
+    }
+}
+
+fn visibility(visible: bool) -> &'static str {
+    if visible {
+        "visible"
+    } else {
+        "hidden"
+    }
+}
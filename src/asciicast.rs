@@ -4,6 +4,7 @@
 use std::collections::HashMap;
 
 #[derive(serde::Deserialize, Debug)]
+#[allow(dead_code)]
 pub struct Theme {
     pub fg: String,
     pub bg: String,
@@ -11,6 +12,7 @@ pub struct Theme {
 }
 
 #[derive(serde::Deserialize, Debug)]
+#[allow(dead_code)]
 pub struct Header {
     pub version: u64,
     pub width: usize,
@@ -24,10 +26,94 @@ pub struct Header {
     pub theme: Option<Theme>,
 }
 
+/// Terminal of an [asciicast v3] header.
+///
+/// [asciicast v3]: https://docs.asciinema.org/manual/asciicast/v3/
+#[derive(serde::Deserialize, Debug)]
+#[allow(dead_code)]
+pub struct Term {
+    pub cols: usize,
+    pub rows: usize,
+    #[serde(rename = "type")]
+    pub term_type: Option<String>,
+    pub theme: Option<Theme>,
+}
+
+/// [asciicast v3] header.
+///
+/// [asciicast v3]: https://docs.asciinema.org/manual/asciicast/v3/
+#[derive(serde::Deserialize, Debug)]
+pub struct HeaderV3 {
+    pub version: u64,
+    pub term: Term,
+    pub timestamp: Option<u128>,
+    pub idle_time_limit: Option<f64>,
+    pub command: Option<String>,
+    pub title: Option<String>,
+    pub env: Option<HashMap<String, String>>,
+}
+
+impl HeaderV3 {
+    /// Convert to a v2 header.
+    pub fn into_v2(self) -> Header {
+        let mut env: Option<HashMap<String, String>> = self.env;
+        if let Some(term_type) = self.term.term_type {
+            env.get_or_insert_with(HashMap::new)
+                .entry("TERM".to_string())
+                .or_insert(term_type);
+        }
+        Header {
+            version: self.version,
+            width: self.term.cols,
+            height: self.term.rows,
+            timestamp: self.timestamp,
+            duration: None,
+            idle_time_limit: self.idle_time_limit,
+            command: self.command,
+            title: self.title,
+            env,
+            theme: self.term.theme,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct Version {
+    version: u64,
+}
+
+/// Whether the first line of an asciicast starts a v1 document.
+///
+/// v1 is a single JSON document, which is usually split over multiple lines,
+/// so the first line is either the whole document, or the start of an object
+/// that is not terminated.
+pub fn is_v1(line: &str) -> bool {
+    match serde_json::from_str::<Version>(line) {
+        Ok(Version { version }) => version == 1,
+        Err(e) => e.is_eof() && line.trim_start().starts_with('{'),
+    }
+}
+
+/// Deserialize the header line of a v2 or v3 asciicast.
+///
+/// v3 headers are converted to v2.
+pub fn header(line: &str) -> serde_json::Result<Header> {
+    match serde_json::from_str::<Version>(line) {
+        Ok(Version { version: 3 }) => Ok(serde_json::from_str::<HeaderV3>(line)?.into_v2()),
+        _ => serde_json::from_str(line),
+    }
+}
+
 #[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Hash, Copy, Clone)]
 pub enum EventType {
     Input,
     Output,
+    /// Marker, with a label as the data.
+    Marker,
+    /// Terminal resize, with the size as `COLSxROWS` data.
+    Resize,
+    /// Exit status of the recorded process, asciicast v3 only.
+    Exit,
 }
 
 impl<'de> serde::de::Deserialize<'de> for EventType {
@@ -38,21 +124,23 @@ impl<'de> serde::de::Deserialize<'de> for EventType {
         match <char>::deserialize(deserializer) {
             Ok('i') => Ok(EventType::Input),
             Ok('o') => Ok(EventType::Output),
+            Ok('m') => Ok(EventType::Marker),
+            Ok('r') => Ok(EventType::Resize),
+            Ok('x') => Ok(EventType::Exit),
             Ok(x) => Err(serde::de::Error::invalid_value(
                 serde::de::Unexpected::Char(x),
-                &"an 'i' or 'o'",
+                &"an 'i', 'o', 'm', 'r' or 'x'",
             )),
             Err(e) => Err(e),
         }
     }
 }
 
-#[derive(serde::Deserialize, Debug)]
+#[derive(serde::Deserialize, Debug, Clone)]
 pub struct Event(f64, EventType, String);
 
 impl Event {
     /// Create a new event.
-    #[allow(dead_code)]
     pub fn new<T>(time: f64, etype: EventType, data: T) -> Event
     where
         T: ToString,
@@ -66,15 +154,76 @@ impl Event {
     }
 
     /// Get the event type.
-    #[allow(dead_code)]
     pub fn event_type(&self) -> EventType {
         self.1
     }
 
+    /// Offset the time of the event, used to convert asciicast v3 intervals
+    /// to absolute times.
+    pub fn offset(mut self, time: f64) -> Event {
+        self.0 += time;
+        self
+    }
+
     /// Get the event data.
     pub fn event_data(&self) -> &str {
         self.2.as_str()
     }
+
+    /// Get the columns and rows of a resize event.
+    ///
+    /// Returns `None` if the data is not a valid size.
+    pub fn size(&self) -> Option<(usize, usize)> {
+        let (cols, rows) = self.2.split_once('x')?;
+        match (cols.parse(), rows.parse()) {
+            (Ok(cols), Ok(rows)) if cols > 0 && rows > 0 => Some((cols, rows)),
+            _ => None,
+        }
+    }
+}
+
+/// [asciicast v1], a single JSON document with all of the output.
+///
+/// [asciicast v1]: https://github.com/asciinema/asciinema/blob/develop/doc/asciicast-v1.md
+#[derive(serde::Deserialize, Debug)]
+pub struct V1 {
+    pub version: u64,
+    pub width: usize,
+    pub height: usize,
+    pub duration: Option<f64>,
+    pub command: Option<String>,
+    pub title: Option<String>,
+    pub env: Option<HashMap<String, String>>,
+    /// Pairs of the delay since the previous output, and the output data.
+    pub stdout: Vec<(f64, String)>,
+}
+
+impl V1 {
+    /// Convert to a v2 header, and output events with absolute times.
+    pub fn into_v2(self) -> (Header, Vec<Event>) {
+        let header: Header = Header {
+            version: self.version,
+            width: self.width,
+            height: self.height,
+            timestamp: None,
+            duration: self.duration,
+            idle_time_limit: None,
+            command: self.command,
+            title: self.title,
+            env: self.env,
+            theme: None,
+        };
+        let mut time: f64 = 0.0;
+        let events: Vec<Event> = self
+            .stdout
+            .into_iter()
+            .map(|(delay, data)| {
+                time += delay;
+                Event::new(time, EventType::Output, data)
+            })
+            .collect();
+        (header, events)
+    }
 }
 
 #[cfg(test)]
@@ -121,4 +270,84 @@ mod test {
         assert_eq!(event.event_data(), expected.event_data());
         assert!((event.time() - expected.time()).abs() < 0.0000001);
     }
+
+    #[test]
+    fn header_v3() {
+        let json_str: &str = r##"{
+            "version": 3,
+            "term": {
+                "cols": 80,
+                "rows": 24,
+                "type": "xterm-256color",
+                "theme": {"fg": "#ffffff", "bg": "#000000", "palette": "#000000:#dd3c69"}
+            },
+            "timestamp": 1504467315,
+            "idle_time_limit": 2.5,
+            "env": {"SHELL": "/bin/zsh"}
+        }"##;
+        let v2: Header = super::header(r#"{"version": 2, "width": 20, "height": 4}"#).unwrap();
+        assert_eq!(v2.width, 20);
+
+        let header: Header = super::header(json_str).unwrap();
+        assert_eq!(header.version, 3);
+        assert_eq!(header.width, 80);
+        assert_eq!(header.height, 24);
+        assert_eq!(header.timestamp, Some(1504467315));
+        assert_eq!(header.idle_time_limit, Some(2.5));
+        let mut map: HashMap<String, String> = HashMap::new();
+        map.insert("TERM".into(), "xterm-256color".into());
+        map.insert("SHELL".into(), "/bin/zsh".into());
+        assert_eq!(header.env, Some(map));
+        assert_eq!(header.theme.unwrap().fg, "#ffffff");
+    }
+
+    #[test]
+    fn marker_and_resize() {
+        let marker: Event = serde_json::from_str(r#"[1.5, "m", "intro"]"#).unwrap();
+        assert_eq!(marker.event_type(), EventType::Marker);
+        assert_eq!(marker.event_data(), "intro");
+
+        let resize: Event = serde_json::from_str(r#"[2.0, "r", "100x40"]"#).unwrap();
+        assert_eq!(resize.event_type(), EventType::Resize);
+        assert_eq!(resize.size(), Some((100, 40)));
+        assert_eq!(Event::new(0.0, EventType::Resize, "100").size(), None);
+        assert_eq!(Event::new(0.0, EventType::Resize, "0x40").size(), None);
+    }
+
+    #[test]
+    fn detect_v1() {
+        assert!(is_v1("{\n"));
+        assert!(is_v1("{\"version\": 1,\n"));
+        assert!(is_v1(
+            r#"{"version": 1, "width": 80, "height": 24, "stdout": []}"#
+        ));
+        assert!(!is_v1(r#"{"version": 2, "width": 80, "height": 24}"#));
+        assert!(!is_v1(r#"{"version": 2, "width": }"#));
+        assert!(!is_v1("[0.1, \"o\", \"a\"]"));
+        assert!(!is_v1(""));
+    }
+
+    #[test]
+    fn v1() {
+        let json_str: &str = r#"{
+            "version": 1,
+            "width": 80,
+            "height": 24,
+            "duration": 1.5,
+            "command": "/bin/zsh",
+            "title": "",
+            "env": {"TERM": "xterm-256color", "SHELL": "/bin/zsh"},
+            "stdout": [[0.25, "$ "], [1.0, "ls"], [0.25, "\r\n"]]
+        }"#;
+        let v1: V1 = serde_json::from_str(json_str).unwrap();
+        let (header, events) = v1.into_v2();
+        assert_eq!(header.version, 1);
+        assert_eq!(header.width, 80);
+        assert_eq!(header.height, 24);
+        assert_eq!(header.duration, Some(1.5));
+        let times: Vec<f64> = events.iter().map(|event| event.time()).collect();
+        assert_eq!(times, vec![0.25, 1.25, 1.5]);
+        assert_eq!(events[1].event_data(), "ls");
+        assert_eq!(events[2].event_type(), EventType::Output);
+    }
 }
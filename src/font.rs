@@ -0,0 +1,145 @@
+//! Font embedding and glyph outlines.
+use anyhow::Context;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+    path::Path,
+};
+
+/// Font family name of the embedded font.
+pub const EMBEDDED_FAMILY: &str = "cast2svg";
+
+/// Create a CSS `@font-face` rule that embeds a font file.
+///
+/// TrueType and OpenType fonts are subset to the glyphs required for `chars`.
+/// WOFF and WOFF2 fonts are already compressed, they are embedded as-is.
+pub fn font_face(path: &Path, chars: &BTreeSet<char>) -> anyhow::Result<String> {
+    let data: Vec<u8> = std::fs::read(path)
+        .with_context(|| format!("Failed to read font from {}", path.to_string_lossy()))?;
+
+    let (mime, format, data): (&str, &str, Vec<u8>) = match data.get(0..4) {
+        Some(b"wOF2") => {
+            log::warn!("WOFF2 fonts cannot be subset, embedding the entire font");
+            ("font/woff2", "woff2", data)
+        }
+        Some(b"wOFF") => {
+            log::warn!("WOFF fonts cannot be subset, embedding the entire font");
+            ("font/woff", "woff", data)
+        }
+        Some(b"OTTO") => ("font/otf", "opentype", subset(&data, chars)?),
+        _ => ("font/ttf", "truetype", subset(&data, chars)?),
+    };
+    log::debug!("embedded font size = {} bytes", data.len());
+
+    Ok(format!(
+        "@font-face{{font-family:{};src:url(data:{};base64,{}) format(\"{}\")}}",
+        EMBEDDED_FAMILY,
+        mime,
+        base64::encode(data),
+        format
+    ))
+}
+
+fn parse(data: &[u8]) -> anyhow::Result<ttf_parser::Face<'_>> {
+    ttf_parser::Face::from_slice(data, 0)
+        .map_err(|e| anyhow::anyhow!("Failed to parse font: {}", e))
+}
+
+/// Subset a TrueType or OpenType font to the glyphs required for `chars`.
+fn subset(data: &[u8], chars: &BTreeSet<char>) -> anyhow::Result<Vec<u8>> {
+    let face = parse(data)?;
+
+    // glyph 0 is .notdef, which must always be present
+    let mut glyphs: Vec<u16> = vec![0];
+    for ch in chars {
+        match face.glyph_index(*ch) {
+            Some(id) => glyphs.push(id.0),
+            None => log::warn!("Font is missing a glyph for {:?}", ch),
+        }
+    }
+    glyphs.sort_unstable();
+    glyphs.dedup();
+
+    subsetter::subset(data, 0, subsetter::Profile::pdf(&glyphs))
+        .map_err(|e| anyhow::anyhow!("Failed to subset font: {:?}", e))
+}
+
+/// Glyph outlines, for rendering text as SVG paths.
+pub struct Glyphs {
+    /// Outline index for each character.
+    ///
+    /// This is `None` for characters without an outline, such as space.
+    index: HashMap<char, Option<usize>>,
+    /// SVG path data for each outline, in font units, with the y-axis pointing
+    /// up.
+    pub outlines: Vec<String>,
+    /// Font units per em.
+    pub units_per_em: f64,
+}
+
+impl Glyphs {
+    /// Load the outlines required for `chars` from a TrueType or OpenType
+    /// font file.
+    pub fn load(path: &Path, chars: &BTreeSet<char>) -> anyhow::Result<Glyphs> {
+        let data: Vec<u8> = std::fs::read(path)
+            .with_context(|| format!("Failed to read font from {}", path.to_string_lossy()))?;
+        let face = parse(&data)?;
+
+        let mut glyphs: Glyphs = Glyphs {
+            index: HashMap::new(),
+            outlines: Vec::new(),
+            units_per_em: f64::from(face.units_per_em()),
+        };
+        let mut glyph_outlines: HashMap<ttf_parser::GlyphId, Option<usize>> = HashMap::new();
+
+        for ch in chars {
+            if ch.is_whitespace() {
+                glyphs.index.insert(*ch, None);
+                continue;
+            }
+            let glyph_id: ttf_parser::GlyphId = face.glyph_index(*ch).unwrap_or_else(|| {
+                log::warn!("Font is missing a glyph for {:?}", ch);
+                ttf_parser::GlyphId(0)
+            });
+            let outline: Option<usize> = *glyph_outlines.entry(glyph_id).or_insert_with(|| {
+                let mut path: PathData = PathData(String::new());
+                face.outline_glyph(glyph_id, &mut path)?;
+                glyphs.outlines.push(path.0);
+                Some(glyphs.outlines.len() - 1)
+            });
+            glyphs.index.insert(*ch, outline);
+        }
+
+        Ok(glyphs)
+    }
+
+    /// Get the outline index for a character.
+    pub fn outline(&self, ch: char) -> Option<usize> {
+        self.index.get(&ch).copied().flatten()
+    }
+}
+
+/// SVG path data builder.
+struct PathData(String);
+
+impl ttf_parser::OutlineBuilder for PathData {
+    fn move_to(&mut self, x: f32, y: f32) {
+        write!(self.0, "M{} {}", x, y).unwrap();
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        write!(self.0, "L{} {}", x, y).unwrap();
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        write!(self.0, "Q{} {} {} {}", x1, y1, x, y).unwrap();
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        write!(self.0, "C{} {} {} {} {} {}", x1, y1, x2, y2, x, y).unwrap();
+    }
+
+    fn close(&mut self) {
+        self.0.push('Z');
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::BTreeMap, convert::TryFrom};
+use std::convert::TryFrom;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Color {
@@ -76,11 +76,83 @@ impl Color {
             Color::Default => (0xcb, 0xbf, 0xbf),
         }
     }
+
+    /// RGB value of the color on a light background.
+    ///
+    /// Colors from the dark palette in [`Color::rgb`] that have a contrast
+    /// ratio below [`MIN_LIGHT_CONTRAST`] against white are darkened until
+    /// they do, keeping their hue.
+    pub fn light_rgb(&self) -> (u8, u8, u8) {
+        if *self == Color::Default {
+            return (0x26, 0x26, 0x26);
+        }
+        let (r, g, b) = self.rgb();
+        let scale = |scale: u16| -> (u8, u8, u8) {
+            let channel = |c: u8| ((c as u16) * scale / 255) as u8;
+            (channel(r), channel(g), channel(b))
+        };
+        // largest scale with enough contrast, black always has enough
+        let (mut low, mut high): (u16, u16) = (0, 255);
+        while low < high {
+            let mid: u16 = (low + high).div_ceil(2);
+            if contrast(scale(mid), (0xff, 0xff, 0xff)) >= MIN_LIGHT_CONTRAST {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        scale(low)
+    }
+}
+
+/// Minimum contrast ratio of text on a light background.
+///
+/// This is the [WCAG] minimum for normal text.
+///
+/// [WCAG]: https://www.w3.org/TR/WCAG21/#contrast-minimum
+pub const MIN_LIGHT_CONTRAST: f64 = 4.5;
+
+/// Relative luminance of an sRGB color.
+fn luminance((r, g, b): (u8, u8, u8)) -> f64 {
+    let linear = |c: u8| {
+        let c: f64 = (c as f64) / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
+
+/// Contrast ratio between two sRGB colors.
+pub fn contrast(a: (u8, u8, u8), b: (u8, u8, u8)) -> f64 {
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
 }
 
 #[test]
 fn color() {
+    const WHITE: (u8, u8, u8) = (0xff, 0xff, 0xff);
     assert_eq!(Color::Indexed(226).rgb(), (0xd2, 0xd2, 0));
+    assert_eq!(Color::White.light_rgb(), Color::Indexed(7).light_rgb());
+    // pale colors are darkened
+    for color in [
+        Color::Indexed(226),
+        Color::Indexed(231),
+        Color::Indexed(255),
+        Color::White,
+        Color::BrightYellow,
+        Color::Rgb(0xd3, 0xd7, 0xcf),
+    ] {
+        assert!(contrast(color.light_rgb(), WHITE) >= MIN_LIGHT_CONTRAST);
+    }
+    let (r, g, b) = Color::Indexed(226).light_rgb();
+    assert_eq!(r, g);
+    assert_eq!(b, 0);
+    // dark colors are unchanged
+    assert_eq!(Color::Red.light_rgb(), Color::Red.rgb());
+    assert_eq!(Color::Blue.light_rgb(), Color::Blue.rgb());
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
@@ -188,6 +260,132 @@ impl Default for Symbol {
     }
 }
 
+/// Create the symbols for a frame buffer.
+fn symbols(buf: &[FrameCell], width: usize, height: usize) -> Vec<Symbol> {
+    let mut symbols: Vec<Symbol> = Vec::new();
+    let mut insert = |symbol: Symbol| symbols.push(symbol);
+
+    for row in 0..height {
+        let mut symbol: Symbol = Symbol::default();
+        let mut previous: FrameCell = FrameCell::default();
+
+        for column in 0..width {
+            let idx: usize = row * width + column;
+            let current: FrameCell = buf[idx];
+
+            if let Some(ch) = current.ch {
+                // left strip spaces
+                if ch == ' ' && symbol.text.is_empty() {
+                    continue;
+                }
+                if symbol.text.is_empty() {
+                    symbol.x = column;
+                    symbol.y = row;
+                    symbol.fg = current.fg;
+                    symbol.intensity = current.intensity;
+                    symbol.text.push(ch);
+                } else if current.attr_eq(&previous) {
+                    debug_assert!(!symbol.text.is_empty());
+                    symbol.text.push(ch);
+                } else {
+                    log::trace!(
+                        "Ending symbol; previous does not match. symbol={}",
+                        symbol.text
+                    );
+                    insert(symbol.clone());
+                    if ch == ' ' {
+                        symbol.text = String::new();
+                    } else {
+                        symbol.text = String::from(ch);
+                    }
+                    symbol.x = column;
+                    symbol.y = row;
+                    symbol.fg = current.fg;
+                    symbol.intensity = current.intensity;
+                }
+            } else if !symbol.text.is_empty() {
+                log::trace!("Ending symbol; unused cell. symbol={}", symbol.text);
+                insert(symbol.clone());
+                symbol.text = String::new();
+            }
+
+            previous = current;
+        }
+
+        if !symbol.text.is_empty() {
+            log::trace!("Ending symbol; end of row. symbol={}", symbol.text);
+            insert(symbol);
+        }
+    }
+
+    symbols
+}
+
+/// Where frames smaller than the viewport are placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum Anchor {
+    /// Pad on the right and bottom.
+    TopLeft,
+    /// Pad evenly on all sides.
+    Center,
+    /// Pad on the right and top, keeping the last row at the bottom.
+    BottomLeft,
+}
+
+/// Size of the rendered terminal, in columns and rows.
+///
+/// This is the largest size of the frames when the terminal is resized.
+#[derive(Debug, Clone, Copy)]
+pub struct Viewport {
+    pub width: usize,
+    pub height: usize,
+    pub anchor: Anchor,
+}
+
+impl Viewport {
+    /// Offset in columns and rows of a frame with the given size.
+    fn offset(&self, width: usize, height: usize) -> (usize, usize) {
+        let x: usize = self.width.saturating_sub(width);
+        let y: usize = self.height.saturating_sub(height);
+        match self.anchor {
+            Anchor::TopLeft => (0, 0),
+            Anchor::Center => (x / 2, y / 2),
+            Anchor::BottomLeft => (0, y),
+        }
+    }
+
+    /// Create the symbols for a frame buffer, placed in the viewport.
+    fn symbols(&self, buf: &[FrameCell], width: usize, height: usize) -> Vec<Symbol> {
+        let (x, y) = self.offset(width, height);
+        let mut symbols: Vec<Symbol> = symbols(buf, width, height);
+        if x != 0 || y != 0 {
+            for symbol in symbols.iter_mut() {
+                symbol.x += x;
+                symbol.y += y;
+            }
+        }
+        symbols
+    }
+}
+
+/// A copy of a frame buffer.
+///
+/// This can be sent to another thread to create symbols, while the next frames
+/// are reconstructed.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    width: usize,
+    height: usize,
+    buf: Vec<FrameCell>,
+}
+
+impl Snapshot {
+    /// Create the symbols for the frame, placed in the viewport.
+    pub fn symbols(&self, viewport: &Viewport) -> Vec<Symbol> {
+        viewport.symbols(&self.buf, self.width, self.height)
+    }
+}
+
 /// A asciicast frame.
 pub struct Frame {
     /// x cursor position, zero index.
@@ -249,67 +447,48 @@ impl Frame {
         assert_ne!(self.y, self.y_max);
     }
 
-    /// Insert symbols for the frame into a multimap.
-    pub fn insert_symbols(&self, map: &mut BTreeMap<Symbol, Vec<usize>>, frame_number: usize) {
-        let mut insert = |symbol: Symbol| {
-            if let Some(v) = map.get_mut(&symbol) {
-                v.push(frame_number);
-            } else {
-                map.insert(symbol, vec![frame_number]);
-            }
-        };
-
-        for row in 0..self.y_max {
-            let mut symbol: Symbol = Symbol::default();
-            let mut previous: FrameCell = FrameCell::default();
-
-            for column in 0..self.x_max {
-                let idx: usize = row * self.x_max + column;
-                let current: FrameCell = self.buf[idx];
-
-                if let Some(ch) = current.ch {
-                    // left strip spaces
-                    if ch == ' ' && symbol.text.is_empty() {
-                        continue;
-                    }
-                    if symbol.text.is_empty() {
-                        symbol.x = column;
-                        symbol.y = row;
-                        symbol.fg = current.fg;
-                        symbol.intensity = current.intensity;
-                        symbol.text.push(ch);
-                    } else if current.attr_eq(&previous) {
-                        debug_assert!(!symbol.text.is_empty());
-                        symbol.text.push(ch);
-                    } else {
-                        log::trace!(
-                            "Ending symbol; previous does not match. symbol={}",
-                            symbol.text
-                        );
-                        insert(symbol.clone());
-                        if ch == ' ' {
-                            symbol.text = String::new();
-                        } else {
-                            symbol.text = String::from(ch);
-                        }
-                        symbol.x = column;
-                        symbol.y = row;
-                        symbol.fg = current.fg;
-                        symbol.intensity = current.intensity;
-                    }
-                } else if !symbol.text.is_empty() {
-                    log::trace!("Ending symbol; unused cell. symbol={}", symbol.text);
-                    insert(symbol.clone());
-                    symbol.text = String::new();
+    /// Resize the frame, as a terminal does when its window is resized.
+    ///
+    /// Rows are clipped on the right or padded with empty cells.
+    /// Rows are removed from the top if the cursor would be below the new
+    /// height, otherwise from the bottom.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        log::debug!(
+            "Resizing frame from {}x{} to {}x{}",
+            self.x_max,
+            self.y_max,
+            width,
+            height
+        );
+        let top: usize = (self.y + 1).saturating_sub(height);
+        let mut buf: Vec<FrameCell> = Vec::with_capacity(width * height);
+        for row in top..top + height {
+            for column in 0..width {
+                if row < self.y_max && column < self.x_max {
+                    buf.push(self.buf[self.buffer_row_index(row) + column]);
+                } else {
+                    buf.push(FrameCell::default());
                 }
-
-                previous = current;
             }
+        }
+        self.buf = buf;
+        self.x_max = width;
+        self.y_max = height;
+        self.x = self.x.min(width - 1);
+        self.y -= top;
+    }
 
-            if !symbol.text.is_empty() {
-                log::trace!("Ending symbol; end of row. symbol={}", symbol.text);
-                insert(symbol);
-            }
+    /// Create the symbols for the frame, placed in the viewport.
+    pub fn symbols(&self, viewport: &Viewport) -> Vec<Symbol> {
+        viewport.symbols(&self.buf, self.x_max, self.y_max)
+    }
+
+    /// Copy the frame buffer, to create symbols for it later.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            width: self.x_max,
+            height: self.y_max,
+            buf: self.buf.clone(),
         }
     }
 
@@ -370,6 +549,62 @@ impl Frame {
     }
 }
 
+#[test]
+fn resize() {
+    let viewport: Viewport = Viewport {
+        width: 4,
+        height: 2,
+        anchor: Anchor::TopLeft,
+    };
+    let text = |frame: &Frame| -> Vec<(usize, usize, String)> {
+        frame
+            .symbols(&viewport)
+            .into_iter()
+            .map(|symbol| (symbol.x, symbol.y, symbol.text))
+            .collect()
+    };
+    let mut frame: Frame = Frame::new(4, 2);
+    let mut parser: vte::Parser = vte::Parser::new();
+    for byte in b"abcd\r\nef" {
+        parser.advance(&mut frame, *byte);
+    }
+
+    frame.resize(2, 1);
+    assert_eq!(text(&frame), vec![(0, 0, "ef".to_string())]);
+    assert_eq!((frame.x, frame.y), (1, 0));
+
+    frame.resize(3, 2);
+    assert_eq!(text(&frame), vec![(0, 0, "ef".to_string())]);
+    for byte in b"\r\ngh" {
+        parser.advance(&mut frame, *byte);
+    }
+    assert_eq!(
+        text(&frame),
+        vec![(0, 0, "ef".to_string()), (0, 1, "gh".to_string())]
+    );
+}
+
+#[test]
+fn anchor() {
+    let mut frame: Frame = Frame::new(2, 1);
+    let mut parser: vte::Parser = vte::Parser::new();
+    for byte in b"ab" {
+        parser.advance(&mut frame, *byte);
+    }
+    let position = |anchor: Anchor| -> (usize, usize) {
+        let viewport: Viewport = Viewport {
+            width: 7,
+            height: 4,
+            anchor,
+        };
+        let symbols: Vec<Symbol> = frame.symbols(&viewport);
+        (symbols[0].x, symbols[0].y)
+    };
+    assert_eq!(position(Anchor::TopLeft), (0, 0));
+    assert_eq!(position(Anchor::Center), (2, 1));
+    assert_eq!(position(Anchor::BottomLeft), (0, 3));
+}
+
 // This trait was designed for alacritty (a rust terminal emulator).
 // There is a lot of useful information contained in the source of alacritty
 // for how to use this trait.
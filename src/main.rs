@@ -10,39 +10,51 @@
 //!
 //! # Limitations
 //!
-//! * Unable to render a still frame, asciicast files must have at least 2 events.
 //! * Many terminal color commands are ignored.
 //! * Many text attributes (italics, underline) are ignored.
 //!
 //! [svg-term-cli]: https://github.com/marionebl/svg-term-cli
 
+mod animation;
 mod asciicast;
+mod font;
 mod frame;
+mod output;
+mod replay;
+mod stream;
+mod timing;
 
+use animation::{Animation, Animator};
 use anyhow::Context;
-use asciicast::Header;
+use asciicast::{EventType, Header};
 use clap::{Parser, ValueHint};
-use frame::{Frame, Symbol};
+use font::Glyphs;
+use frame::{Anchor, Color, Symbol, Viewport};
 
-use std::io::{BufRead, BufReader, Write};
-use std::{collections::BTreeMap, path::PathBuf};
+use output::Output;
+use rayon::ThreadPool;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
 use std::{
-    collections::HashMap,
-    fs::{File, OpenOptions},
+    collections::{HashMap, HashSet},
+    fs::File,
 };
+use timing::{IdleLimit, Keyframe, Loop, Position, Timeline};
 use xmlwriter::{Indent, XmlWriter};
 
-const FONT_SIZE: f64 = 5.0 / 3.0;
-const HEIGHT_SCALE: f64 = 21.71;
-const WIDTH_SCALE: usize = 10;
-
 /// Create an animated SVG from an ASCIICAST.
 #[derive(Debug, Parser)]
 #[clap(author, version)]
 struct Args {
-    /// Input asciicast file.
+    /// Input asciicast file, reads from stdin if not set or '-'.
+    ///
+    /// gzip and zstd compressed asciicasts are decompressed.
     #[clap(parse(from_os_str), value_hint=ValueHint::FilePath)]
-    input: PathBuf,
+    input: Option<PathBuf>,
     /// Increase logging verbosity.
     #[clap(short, long, parse(from_occurrences))]
     verbose: usize,
@@ -50,8 +62,13 @@ struct Args {
     #[clap(long)]
     indent: bool,
     /// Output file path, outputs to stdout if not set.
+    ///
+    /// The output is gzip compressed if the file extension is 'svgz'.
     #[clap(short, long)]
     output: Option<PathBuf>,
+    /// Gzip compress the output.
+    #[clap(long)]
+    gzip: bool,
     /// Render with window decorations.
     #[clap(long)]
     window: bool,
@@ -61,98 +78,624 @@ struct Args {
     /// Height in rows, defaults to the value in the asciicast header.
     #[clap(long)]
     height: Option<usize>,
+    /// Limit idle time between events to this many seconds, defaults to the
+    /// value in the asciicast header.
+    ///
+    /// A value in the header that is not positive is ignored.
+    #[clap(long)]
+    idle_limit: Option<f64>,
+    /// Start the animation at this time, in seconds, or at the marker with
+    /// this label.
+    #[clap(long)]
+    from: Option<Position>,
+    /// End the animation at this time, in seconds, or at the marker with this
+    /// label.
+    #[clap(long)]
+    to: Option<Position>,
+    /// Playback speed multiplier.
+    #[clap(long, conflicts_with = "duration")]
+    speed: Option<f64>,
+    /// Change the playback speed to play in exactly this many seconds.
+    ///
+    /// The --start-pause and --end-pause are added to this duration.
+    #[clap(long)]
+    duration: Option<f64>,
+    /// Maximum number of frames per second.
+    #[clap(long, conflicts_with = "min-frame-interval")]
+    fps: Option<f64>,
+    /// Minimum time between frames, in seconds.
+    ///
+    /// Events less than this apart are coalesced into a single frame, which
+    /// shows the terminal at the time of its first event.
+    #[clap(long)]
+    min_frame_interval: Option<f64>,
+    /// Hold the first frame for this many seconds.
+    #[clap(long, default_value = "0")]
+    start_pause: f64,
+    /// Hold the last frame for this many seconds.
+    #[clap(long, default_value = "0")]
+    end_pause: f64,
+    /// Number of times to play the animation, or 'infinite'.
+    ///
+    /// A finite animation stops on the last frame.
+    #[clap(long = "loop", value_name = "COUNT", default_value = "infinite")]
+    loop_count: Loop,
+    /// Animation backend.
+    ///
+    /// SMIL is supported by renderers that ignore CSS animations in SVG.
+    /// SMIL animations show each frame by its visibility, and use the stacked
+    /// frame layout instead of the strip frame layout.
+    #[clap(long, arg_enum, default_value = "css")]
+    animation: Animation,
+    /// How frames are laid out in the animation.
+    #[clap(long, arg_enum, default_value = "strip")]
+    frame_layout: FrameLayout,
+    /// Render a still frame of the terminal at this time, in seconds.
+    #[clap(long, group = "still")]
+    at: Option<f64>,
+    /// Render this frame of the animation as a still frame, zero indexed.
+    ///
+    /// Frames are counted as they are animated, after trimming with --from
+    /// and --to, coalescing with --fps, and dropping identical frames.
+    /// Frame 0 is the frame at --from.
+    #[clap(long, group = "still")]
+    frame: Option<usize>,
+    /// Render a still frame of the terminal after the last event.
+    #[clap(long, group = "still")]
+    last: bool,
+    /// Font family, or a comma separated list of font families.
+    #[clap(long, default_value = "Courier New")]
+    font_family: String,
+    /// Embed a TrueType, OpenType, WOFF, or WOFF2 font file.
+    ///
+    /// TrueType and OpenType fonts are subset to the glyphs used in the
+    /// asciicast.
+    #[clap(long, parse(from_os_str), value_hint=ValueHint::FilePath)]
+    embed_font: Option<PathBuf>,
+    /// Render text as paths, with glyph outlines from a TrueType or OpenType
+    /// font file.
+    ///
+    /// The resulting SVG does not require any fonts to view.
+    #[clap(
+        long,
+        parse(from_os_str),
+        value_hint=ValueHint::FilePath,
+        conflicts_with = "embed-font"
+    )]
+    text_to_paths: Option<PathBuf>,
+    /// Font size in pixels.
+    #[clap(long, default_value = "16.67")]
+    font_size: f64,
+    /// Height of each row in pixels.
+    #[clap(long, default_value = "21.71")]
+    line_height: f64,
+    /// Width of each column in pixels.
+    #[clap(long, default_value = "10")]
+    column_width: f64,
+    /// Emit a dark and a light palette, selected by the viewer's preferred
+    /// color scheme.
+    #[clap(long)]
+    adaptive: bool,
+    /// Where to place frames that are smaller than the largest frame, after
+    /// the terminal is resized.
+    #[clap(long, arg_enum, default_value = "top-left")]
+    anchor: Anchor,
+    /// Write frames as they are rendered, instead of keeping the asciicast
+    /// and the frames in memory.
+    ///
+    /// The asciicast is read multiple times.
+    /// Memory still grows with the number of frames and unique symbols, use
+    /// --fps to limit the number of frames of long recordings.
+    /// This is not supported with the delta frame layout, or with stdin.
+    #[clap(long)]
+    stream: bool,
+    /// Number of threads to create symbols and write frames with, or 0 for
+    /// one per CPU.
+    ///
+    /// The output is identical to the output of a single thread.
+    #[clap(short, long, default_value = "1")]
+    jobs: usize,
 }
 
-fn viewbox_dimension(dimension: f64) -> String {
-    let mut ret: String = format!("{:.3}", dimension / 10.0);
-    while ret.ends_with('0') {
-        ret.pop();
+/// Background color for the dark color scheme.
+const DARK_BACKGROUND: (u8, u8, u8) = (0x26, 0x26, 0x26);
+/// Background color for the light color scheme.
+const LIGHT_BACKGROUND: (u8, u8, u8) = (0xff, 0xff, 0xff);
+
+/// Text layout of the terminal grid.
+///
+/// All dimensions are in pixels.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    font_size: f64,
+    line_height: f64,
+    column_width: f64,
+}
+
+impl Layout {
+    /// Width of the terminal grid.
+    fn width(&self, columns: usize) -> f64 {
+        (columns as f64) * self.column_width
     }
-    if ret.ends_with('.') {
-        ret.pop();
+
+    /// Height of the terminal grid.
+    fn height(&self, rows: usize) -> f64 {
+        (rows as f64) * self.line_height
+    }
+
+    /// Text baseline of a row.
+    fn baseline(&self, row: usize) -> f64 {
+        self.height(row) + self.font_size
+    }
+}
+
+/// Format a value with trailing zeros removed.
+fn trimmed(value: f64, precision: usize) -> String {
+    let mut ret: String = format!("{:.*}", precision, value);
+    if ret.contains('.') {
+        while ret.ends_with('0') {
+            ret.pop();
+        }
+        if ret.ends_with('.') {
+            ret.pop();
+        }
     }
     ret
 }
 
-fn read_asciicast(path: PathBuf) -> anyhow::Result<(asciicast::Header, Vec<asciicast::Event>)> {
-    let file = File::open(&path)
-        .with_context(|| format!("Failed to read asciicast from {}", path.to_string_lossy()))?;
-    let mut reader: BufReader<File> = BufReader::new(file);
-
-    let header: asciicast::Header = {
-        let mut line: String = String::new();
-        reader
-            .read_line(&mut line)
-            .with_context(|| "Failed to read header from asciicast")?;
-        serde_json::from_str(line.as_str())
-            .with_context(|| "Failed to deserialize header from asciicast")?
-    };
-    log::debug!("asciicast header = {:#?}", header);
+fn viewbox_dimension(dimension: f64) -> String {
+    trimmed(dimension / 10.0, 3)
+}
 
-    let mut events: Vec<asciicast::Event> = Vec::new();
-    let mut previous_time: f64 = -1.0;
-    for (num, line) in reader.lines().enumerate() {
-        let line_num = num + 2;
-        let data =
-            line.with_context(|| format!("Failed to read line {} from asiicast", line_num))?;
-        let event: asciicast::Event = serde_json::from_str(data.as_str())
-            .with_context(|| format!("Failed to deserialize line {} from asciicast", line_num))?;
-
-        // validate assumption that event times are always increasing
-        if event.time() < previous_time {
-            return Err(anyhow::anyhow!(
-                "asciicast event on line {} went backwards in time",
-                line_num
-            ));
-        } else {
-            previous_time = event.time();
-        }
+/// Events of an asciicast.
+enum Events<R: BufRead> {
+    /// Newline delimited events, read lazily after the header.
+    Lines {
+        lines: std::io::Lines<R>,
+        line_num: usize,
+        previous_time: f64,
+        /// Event times are intervals since the previous event, and lines
+        /// starting with `#` are comments, as in asciicast v3.
+        v3: bool,
+    },
+    /// Events converted from a v1 asciicast.
+    V1(std::vec::IntoIter<asciicast::Event>),
+}
+
+impl<R: BufRead> Iterator for Events<R> {
+    type Item = anyhow::Result<asciicast::Event>;
 
-        events.push(event)
+    fn next(&mut self) -> Option<Self::Item> {
+        let (lines, line_num, previous_time, v3) = match self {
+            Events::Lines {
+                lines,
+                line_num,
+                previous_time,
+                v3,
+            } => (lines, line_num, previous_time, *v3),
+            Events::V1(events) => return events.next().map(Ok),
+        };
+        let line = loop {
+            let line = lines.next()?;
+            *line_num += 1;
+            match line {
+                Ok(data) if v3 && data.starts_with('#') => continue,
+                line => break line,
+            }
+        };
+        let line_num: usize = *line_num;
+        let event = line
+            .with_context(|| format!("Failed to read line {} from asiicast", line_num))
+            .and_then(|data| {
+                serde_json::from_str::<asciicast::Event>(data.as_str()).with_context(|| {
+                    format!("Failed to deserialize line {} from asciicast", line_num)
+                })
+            })
+            .and_then(|event| {
+                // validate assumption that event times are always increasing
+                if v3 && event.time() < 0.0 {
+                    Err(anyhow::anyhow!(
+                        "asciicast event on line {} has a negative interval",
+                        line_num
+                    ))
+                } else if v3 {
+                    let event: asciicast::Event = event.offset(*previous_time);
+                    *previous_time = event.time();
+                    Ok(event)
+                } else if event.time() < *previous_time {
+                    Err(anyhow::anyhow!(
+                        "asciicast event on line {} went backwards in time",
+                        line_num
+                    ))
+                } else {
+                    *previous_time = event.time();
+                    Ok(event)
+                }
+            });
+        Some(event)
+    }
+}
+
+/// Magic bytes at the start of a gzip stream.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+/// Magic bytes at the start of a zstd frame.
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Decompress gzip and zstd input, detected by magic bytes.
+///
+/// Uncompressed input is returned as-is.
+fn decompress<R: BufRead + Send + 'static>(mut reader: R) -> anyhow::Result<Input> {
+    let magic: &[u8] = reader
+        .fill_buf()
+        .with_context(|| "Failed to read header from asciicast")?;
+    if magic.starts_with(GZIP_MAGIC) {
+        log::debug!("Decompressing gzip asciicast");
+        let decoder = flate2::bufread::MultiGzDecoder::new(reader);
+        Ok(Box::new(BufReader::new(decoder)))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        log::debug!("Decompressing zstd asciicast");
+        let decoder = zstd::Decoder::with_buffer(reader)
+            .with_context(|| "Failed to create zstd decoder for asciicast")?;
+        Ok(Box::new(BufReader::new(decoder)))
+    } else {
+        Ok(Box::new(reader))
     }
+}
 
-    let num_events: usize = events.len();
-    const MIN_EVENTS: usize = 2;
-    if num_events < MIN_EVENTS {
+/// Read an asciicast header, decompressing gzip and zstd input.
+///
+/// The events are read lazily from the returned iterator.
+fn read_asciicast<R: BufRead + Send + 'static>(
+    reader: R,
+) -> anyhow::Result<(asciicast::Header, Events<Input>)> {
+    let mut reader: Input = decompress(reader)?;
+    let mut line: String = String::new();
+    reader
+        .read_line(&mut line)
+        .with_context(|| "Failed to read header from asciicast")?;
+    let header: Result<asciicast::Header, serde_json::Error> = asciicast::header(&line);
+
+    if asciicast::is_v1(&line) {
+        let document = std::io::Cursor::new(line).chain(reader);
+        let v1: asciicast::V1 = serde_json::from_reader(document)
+            .with_context(|| "Failed to deserialize asciicast v1")?;
+        return read_asciicast_v1(v1);
+    }
+
+    let header: asciicast::Header =
+        header.with_context(|| "Failed to deserialize header from asciicast")?;
+    log::debug!("asciicast header = {:#?}", header);
+
+    let v3: bool = header.version == 3;
+    let events: Events<Input> = Events::Lines {
+        lines: reader.lines(),
+        line_num: 1,
+        previous_time: if v3 { 0.0 } else { -1.0 },
+        v3,
+    };
+    Ok((header, events))
+}
+
+/// Convert a v1 asciicast to a header and events.
+fn read_asciicast_v1(v1: asciicast::V1) -> anyhow::Result<(asciicast::Header, Events<Input>)> {
+    if v1.version != 1 {
         return Err(anyhow::anyhow!(
-            "The asciicast must have at least {} events, found {}",
-            MIN_EVENTS,
-            num_events
+            "asciicast v{} must have a header on a single line",
+            v1.version
         ));
     }
+    if let Some(idx) = v1.stdout.iter().position(|(delay, _)| *delay < 0.0) {
+        return Err(anyhow::anyhow!(
+            "asciicast v1 output {} went backwards in time",
+            idx
+        ));
+    }
+    let (header, events) = v1.into_v2();
+    log::debug!("asciicast v1 header = {:#?}", header);
+    Ok((header, Events::V1(events.into_iter())))
+}
 
-    Ok((header, events))
+/// Input that an asciicast is read from.
+type Input = Box<dyn BufRead + Send>;
+
+/// Open an asciicast file, or stdin if there is no file.
+fn open_asciicast(path: Option<&Path>) -> anyhow::Result<(asciicast::Header, Events<Input>)> {
+    let reader: Input = match path {
+        Some(path) => {
+            let file = File::open(path).with_context(|| {
+                format!("Failed to read asciicast from {}", path.to_string_lossy())
+            })?;
+            Box::new(BufReader::new(file))
+        }
+        None => Box::new(BufReader::new(std::io::stdin())),
+    };
+    read_asciicast(reader)
 }
 
-/// Create a symbol map from an asciicast.
-///
-/// This function does all the heavy lifting, reconstructing the terminal frame
-/// based on the byte data.
-///
-/// This uses alacritty's [vte] crate to reconstruct the frames.
+/// Layout of the frames of an animation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+enum FrameLayout {
+    /// Frames side by side in a horizontal strip, which slides to each frame.
+    Strip,
+    /// Frames stacked in place, each visible for the duration of its keyframe.
+    ///
+    /// This avoids a very wide strip for long asciicasts.
+    Stacked,
+    /// Each symbol drawn once, visible for every frame it is in.
+    ///
+    /// The output size scales with the number of changes rather than the
+    /// number of frames.
+    Delta,
+}
+
+/// Intervals of time covered by ascending frame numbers.
 ///
-/// The return value is an ordered multimap.
+/// Consecutive frames are merged into a single interval.
+/// An interval containing the last frame lasts until the end of the animation.
+fn frame_intervals(keyframes: &[Keyframe], frames: &[usize]) -> Vec<(f64, Option<f64>)> {
+    let mut intervals: Vec<(f64, Option<f64>)> = Vec::new();
+    let mut frames = frames.iter().peekable();
+    while let Some(&first) = frames.next() {
+        let mut last: usize = first;
+        while let Some(&&next) = frames.peek() {
+            if next != last + 1 {
+                break;
+            }
+            last = next;
+            frames.next();
+        }
+        intervals.push((
+            keyframes[first].time,
+            keyframes.get(last + 1).map(|keyframe| keyframe.time),
+        ));
+    }
+    intervals
+}
+
+/// Select the keyframe to render a still frame for, at a time or after the
+/// last event.
 ///
-/// The key is a symbol, and each value is a vector of frame numbers that the
-/// symbol appears in.
+/// Keyframes are only read up to the selected keyframe.
+/// Returns `None` when the asciicast should be animated, without reading any
+/// keyframes.
+fn still_frame<I>(args: &Args, keyframes: &mut I) -> Option<Keyframe>
+where
+    I: Iterator<Item = Keyframe>,
+{
+    if let Some(at) = args.at {
+        let mut selected: Option<Keyframe> = keyframes.next();
+        for keyframe in keyframes {
+            if keyframe.time > at {
+                break;
+            }
+            selected = Some(keyframe);
+        }
+        selected
+    } else if args.last {
+        keyframes.last()
+    } else {
+        None
+    }
+}
+
+/// Largest terminal size shown in the keyframes, in columns and rows.
 ///
-/// The data is in the multimap to make allow us to deduplicate symbols for each
-/// frame that they appear in later on.
+/// `resizes` are the event index and size of each resize event.
+fn largest_size(
+    header: &Header,
+    resizes: &[(usize, (usize, usize))],
+    keyframes: &[Keyframe],
+) -> (usize, usize) {
+    let mut size: (usize, usize) = (header.width, header.height);
+    let mut largest: (usize, usize) = (0, 0);
+    let mut remaining = resizes.iter().peekable();
+    for keyframe in keyframes {
+        while let Some((_, resized)) = remaining.next_if(|(event, _)| *event <= keyframe.event) {
+            size = *resized;
+        }
+        largest = (largest.0.max(size.0), largest.1.max(size.1));
+    }
+    largest
+}
+
+/// Time of a `--from` or `--to` position.
+fn position_time(
+    position: Option<&Position>,
+    markers: &[(f64, String)],
+) -> anyhow::Result<Option<f64>> {
+    match position {
+        Some(position) => match position.time(markers) {
+            Some(time) => Ok(Some(time)),
+            None => Err(anyhow::anyhow!(
+                "The asciicast has no marker labelled '{}'",
+                position
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Idle limited times of events, read one at a time.
 ///
-/// [vte]: https://github.com/alacritty/vte
-fn symbol_map(header: &Header, events: &[asciicast::Event]) -> BTreeMap<Symbol, Vec<usize>> {
-    let mut frame: Frame = Frame::new(header.width, header.height);
-    let mut parser: vte::Parser = vte::Parser::new();
-    let mut symbol_map: BTreeMap<Symbol, Vec<usize>> = BTreeMap::new();
-    for (event_num, event) in events.iter().enumerate() {
-        log::trace!("Event number {}: x={}, y={}", event_num, frame.x, frame.y);
-        for byte in event.event_data().as_bytes() {
-            parser.advance(&mut frame, *byte)
+/// Reading stops at the first error, which is returned by [`Times::finish`].
+struct Times<'a> {
+    times: Box<dyn Iterator<Item = anyhow::Result<f64>> + 'a>,
+    idle: IdleLimit,
+    error: Option<anyhow::Error>,
+}
+
+impl<'a> Times<'a> {
+    fn new<I>(times: I, idle_limit: Option<f64>) -> Times<'a>
+    where
+        I: Iterator<Item = anyhow::Result<f64>> + 'a,
+    {
+        Times {
+            times: Box::new(times),
+            idle: IdleLimit::new(idle_limit),
+            error: None,
+        }
+    }
+
+    /// Return the error that stopped reading, if any.
+    fn finish(self) -> anyhow::Result<()> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Iterator for Times<'_> {
+    type Item = f64;
+
+    fn next(&mut self) -> Option<f64> {
+        match self.times.next()? {
+            Ok(time) => Some(self.idle.time(time)),
+            Err(error) => {
+                self.error = Some(error);
+                None
+            }
+        }
+    }
+}
+
+/// Unique symbols in an asciicast, and the frames they appear in.
+#[derive(Debug, Default)]
+struct SymbolMap {
+    /// Symbol ids, used to deduplicate symbols.
+    ids: HashMap<Symbol, usize>,
+    /// Symbols and the number of frames they appear in, indexed by symbol id.
+    ///
+    /// Symbol ids are assigned in the order that symbols first appear.
+    symbols: Vec<(Symbol, usize)>,
+    /// Symbol ids in each frame, indexed by frame number.
+    ///
+    /// Only the last frame is kept when streaming.
+    frames: Vec<Vec<usize>>,
+    /// Number of frames inserted.
+    num_frames: usize,
+    streaming: bool,
+}
+
+impl SymbolMap {
+    /// Create a symbol map that only keeps the symbol ids of the last frame.
+    fn streaming() -> SymbolMap {
+        SymbolMap {
+            streaming: true,
+            ..SymbolMap::default()
+        }
+    }
+
+    /// Insert the symbols of the next frame.
+    ///
+    /// Returns `false` without inserting a frame if the symbols are identical
+    /// to the previous frame.
+    fn insert_frame(&mut self, symbols: Vec<Symbol>) -> bool {
+        let mut ids: Vec<usize> = Vec::with_capacity(symbols.len());
+        for symbol in symbols.into_iter() {
+            let unique: &mut Vec<(Symbol, usize)> = &mut self.symbols;
+            let id: usize = *self.ids.entry(symbol).or_insert_with_key(|symbol| {
+                unique.push((symbol.clone(), 0));
+                unique.len() - 1
+            });
+            ids.push(id);
+        }
+        // an identical frame only contains existing symbols
+        if self.frames.last() == Some(&ids) {
+            return false;
         }
+        for &id in ids.iter() {
+            self.symbols[id].1 += 1;
+        }
+        if self.streaming {
+            self.frames.clear();
+        }
+        self.frames.push(ids);
+        self.num_frames += 1;
+        true
+    }
+
+    /// Symbol ids of existing symbols.
+    fn ids(&self, symbols: &[Symbol]) -> Vec<usize> {
+        symbols.iter().map(|symbol| self.ids[symbol]).collect()
+    }
+
+    /// Iterate over symbols and the number of frames they appear in, in
+    /// symbol id order.
+    fn iter(&self) -> std::slice::Iter<'_, (Symbol, usize)> {
+        self.symbols.iter()
+    }
+
+    /// Iterate over symbols in symbol id order.
+    fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter().map(|(symbol, _)| symbol)
+    }
 
-        frame.insert_symbols(&mut symbol_map, event_num);
+    /// Symbol ids in a frame.
+    fn frame(&self, frame_num: usize) -> &[usize] {
+        debug_assert!(!self.streaming);
+        &self.frames[frame_num]
+    }
+
+    /// Frames that each symbol appears in, indexed by symbol id.
+    fn symbol_frames(&self) -> Vec<Vec<usize>> {
+        debug_assert!(!self.streaming);
+        let mut symbol_frames: Vec<Vec<usize>> = vec![Vec::new(); self.symbols.len()];
+        for (frame_num, ids) in self.frames.iter().enumerate() {
+            for &id in ids.iter() {
+                symbol_frames[id].push(frame_num);
+            }
+        }
+        symbol_frames
     }
-    symbol_map
+}
+
+/// Create a symbol map from an asciicast.
+///
+/// The symbols of each frame are deduplicated, with an index from frame number
+/// to symbol ids so that frames can be written in time linear to their size.
+///
+/// Keyframes that are identical to the previous keyframe are removed, which
+/// extends the duration of the previous keyframe.
+fn symbol_map<I>(
+    header: &Header,
+    viewport: &Viewport,
+    events: I,
+    keyframes: &mut Vec<Keyframe>,
+    pool: Option<&ThreadPool>,
+    mut symbol_map: SymbolMap,
+) -> anyhow::Result<SymbolMap>
+where
+    I: IntoIterator<Item = anyhow::Result<asciicast::Event>>,
+    I::IntoIter: Send,
+{
+    // whether to keep each keyframe, removed in place to avoid a second list
+    let mut keep: Vec<bool> = Vec::with_capacity(keyframes.len());
+    let identity = |symbols: Vec<Symbol>| symbols;
+    replay::replay(
+        header,
+        viewport,
+        events,
+        keyframes,
+        pool,
+        identity,
+        |keyframe, symbols| {
+            let inserted: bool = symbol_map.insert_frame(symbols);
+            if !inserted {
+                log::trace!("Dropping keyframe for event {}, no changes", keyframe.event);
+            }
+            keep.push(inserted);
+            Ok(())
+        },
+    )?;
+    let num_keyframes: usize = keyframes.len();
+    let mut keep = keep.into_iter();
+    keyframes.retain(|_| keep.next().unwrap());
+    log::debug!(
+        "Dropped {} identical keyframes",
+        num_keyframes - keyframes.len()
+    );
+    Ok(symbol_map)
 }
 
 /// Color attributes in the `color_map`.
@@ -173,6 +716,47 @@ impl ColorAttribute {
     }
 }
 
+/// Key of a color in the `color_map`, the RGB values on a dark and on a
+/// light background.
+///
+/// Colors with the same dark value can have different light values, so both
+/// are needed to share a fill.
+type ColorKey = ((u8, u8, u8), (u8, u8, u8));
+
+fn color_key(color: &Color) -> ColorKey {
+    (color.rgb(), color.light_rgb())
+}
+
+/// Create a list of colors referenced by CSS custom properties.
+///
+/// This is used for adaptive color schemes, where the fill of each color is
+/// `var(--c0)`, `var(--c1)`, etc. instead of a literal value, and the values
+/// of the custom properties are switched with the preferred color scheme.
+///
+/// The index of each color in the list is the number of the custom property.
+fn color_vars(symbol_map: &SymbolMap) -> Vec<ColorKey> {
+    let mut colors: HashSet<Color> = HashSet::new();
+    let mut color_vars: Vec<ColorKey> = Vec::new();
+    for symbol in symbol_map.symbols() {
+        if colors.insert(symbol.fg) {
+            let key: ColorKey = color_key(&symbol.fg);
+            if !color_vars.contains(&key) {
+                color_vars.push(key);
+            }
+        }
+    }
+    color_vars
+}
+
+/// Fill value for a color, either a literal or a CSS custom property.
+fn fill(key: ColorKey, color_vars: Option<&[ColorKey]>) -> String {
+    let ((r, g, b), _) = key;
+    match color_vars.and_then(|vars| vars.iter().position(|var| *var == key)) {
+        Some(idx) => format!("var(--c{})", idx),
+        None => format!("#{:02x}{:02x}{:02x}", r, g, b),
+    }
+}
+
 /// Create a color map from an asciicast.
 ///
 /// This function gets a little crazy.
@@ -181,17 +765,18 @@ impl ColorAttribute {
 /// Referencing by CSS class is only more efficient (in terms of file size)
 /// if the color is used more than once.
 ///
-/// The output of this is a map with a key of the (R, G, B) values, and a value
-/// of the element name, and the element value.
+/// The output of this is a map with a key of the [`ColorKey`], and a value of
+/// the element name, and the element value.
 fn color_map(
-    symbol_map: &BTreeMap<Symbol, Vec<usize>>,
-) -> HashMap<(u8, u8, u8), (ColorAttribute, String)> {
-    let mut color_map: HashMap<(u8, u8, u8), (ColorAttribute, String)> = HashMap::new();
+    symbol_map: &SymbolMap,
+    color_vars: Option<&[ColorKey]>,
+) -> BTreeMap<ColorKey, (ColorAttribute, String)> {
+    let mut color_map: BTreeMap<ColorKey, (ColorAttribute, String)> = BTreeMap::new();
     let mut class: String = String::from("a");
 
-    for symbol in symbol_map.keys() {
-        let (r, g, b) = symbol.fg.rgb();
-        if let Some((attribute, attribute_value)) = color_map.get_mut(&(r, g, b)) {
+    for symbol in symbol_map.symbols() {
+        let key: ColorKey = color_key(&symbol.fg);
+        if let Some((attribute, attribute_value)) = color_map.get_mut(&key) {
             // more than one symbol references this color, move to style
             if *attribute == ColorAttribute::Style {
                 *attribute = ColorAttribute::Class;
@@ -210,10 +795,10 @@ fn color_map(
             }
         } else {
             color_map.insert(
-                (r, g, b),
+                key,
                 (
                     ColorAttribute::Style,
-                    format!("fill: #{:02x}{:02x}{:02x}", r, g, b),
+                    format!("fill: {}", fill(key, color_vars)),
                 ),
             );
         }
@@ -223,49 +808,297 @@ fn color_map(
 
 fn write_text_element(
     svg: &mut XmlWriter,
-    color_map: &HashMap<(u8, u8, u8), (ColorAttribute, String)>,
+    layout: &Layout,
+    color_map: &BTreeMap<ColorKey, (ColorAttribute, String)>,
+    glyphs: Option<&Glyphs>,
     symbol: &Symbol,
 ) {
+    if let Some(glyphs) = glyphs {
+        write_glyphs_element(svg, layout, color_map, glyphs, symbol);
+        return;
+    }
+
     svg.start_element("text");
-    let (atrribute_name, attribute_value) = color_map.get(&symbol.fg.rgb()).unwrap();
+    let (atrribute_name, attribute_value) = color_map.get(&color_key(&symbol.fg)).unwrap();
     svg.write_attribute(atrribute_name.to_str(), attribute_value);
     if symbol.x != 0 {
-        svg.write_attribute_fmt("x", format_args!("{}", symbol.x));
+        svg.write_attribute("x", &viewbox_dimension(layout.width(symbol.x)));
     }
-    svg.write_attribute_fmt(
-        "y",
-        format_args!("{:.2}", (symbol.y as f64) * HEIGHT_SCALE / 10.0 + FONT_SIZE),
-    );
+    svg.write_attribute_fmt("y", format_args!("{:.2}", layout.baseline(symbol.y) / 10.0));
     svg.set_preserve_whitespaces(true);
     svg.write_text(&symbol.escaped_text());
     svg.set_preserve_whitespaces(false);
     svg.end_element(); // text
 }
 
+/// Write a symbol as a group of references to glyph outlines.
+fn write_glyphs_element(
+    svg: &mut XmlWriter,
+    layout: &Layout,
+    color_map: &BTreeMap<ColorKey, (ColorAttribute, String)>,
+    glyphs: &Glyphs,
+    symbol: &Symbol,
+) {
+    svg.start_element("g");
+    let (atrribute_name, attribute_value) = color_map.get(&color_key(&symbol.fg)).unwrap();
+    svg.write_attribute(atrribute_name.to_str(), attribute_value);
+    let y: String = format!("{:.2}", layout.baseline(symbol.y) / 10.0);
+    for (idx, ch) in symbol.text.chars().enumerate() {
+        if let Some(outline) = glyphs.outline(ch) {
+            svg.start_element("use");
+            svg.write_attribute_fmt("xlink:href", format_args!("#g{}", outline));
+            let column: usize = symbol.x + idx;
+            if column != 0 {
+                svg.write_attribute("x", &viewbox_dimension(layout.width(column)));
+            }
+            svg.write_attribute("y", &y);
+            svg.end_element(); // use
+        }
+    }
+    svg.end_element(); // g
+}
+
 fn main() -> anyhow::Result<()> {
     // CLI arguments and logging setup
-    let args = Args::parse();
+    let mut args = Args::parse();
     stderrlog::new()
         .module(module_path!())
         .verbosity(args.verbose)
         .init()
         .unwrap();
 
+    if args.animation == Animation::Smil && args.frame_layout == FrameLayout::Strip {
+        log::info!("Using the stacked frame layout, SMIL animates the visibility of each frame");
+        args.frame_layout = FrameLayout::Stacked;
+    }
+
+    if args.stream && args.frame_layout == FrameLayout::Delta {
+        return Err(anyhow::anyhow!("The delta frame layout cannot be streamed"));
+    }
+
     // handle asciicast input
-    let (header, events) = read_asciicast(args.input)?;
-    let num_events: usize = events.len();
-    let first_event_time: f64 = events.first().unwrap().time();
-    let last_event_time: f64 = events.last().unwrap().time();
-    debug_assert!(
-        last_event_time > first_event_time,
-        "last event occured before first event"
-    );
-    let duration: f64 = last_event_time - first_event_time;
-    debug_assert!(duration.is_sign_positive());
+    let input: Option<&Path> = args.input.as_deref().filter(|path| *path != Path::new("-"));
+    if args.stream && input.is_none() {
+        return Err(anyhow::anyhow!(
+            "Streaming requires an input file, stdin can only be read once"
+        ));
+    }
+    let (header, events) = open_asciicast(input)?;
+    let positive = |value: f64| value.is_finite() && value > 0.0;
+    let idle_limit: Option<f64> = match args.idle_limit {
+        Some(limit) if !positive(limit) => {
+            return Err(anyhow::anyhow!(
+                "Idle limit must be positive, found {}",
+                limit
+            ));
+        }
+        Some(limit) => Some(limit),
+        None => header.idle_time_limit.filter(|&limit| {
+            if !positive(limit) {
+                log::debug!("Ignoring the idle time limit of {} in the header", limit);
+            }
+            positive(limit)
+        }),
+    };
+    if let Some(limit) = idle_limit {
+        log::debug!("Limiting idle time to {}s", limit);
+    }
+    let mut idle: IdleLimit = IdleLimit::new(idle_limit);
+    // time and label of each marker
+    let mut markers: Vec<(f64, String)> = Vec::new();
+    // event index and size of each resize
+    let mut resizes: Vec<(usize, (usize, usize))> = Vec::new();
+    let mut num_events: usize = 0;
+    // when streaming the events are read again instead of kept in memory
+    let mut kept: Vec<asciicast::Event> = Vec::new();
+    for event in events {
+        let event: asciicast::Event = event?;
+        let time: f64 = idle.time(event.time());
+        if event.event_type() == EventType::Marker {
+            markers.push((time, event.event_data().to_string()));
+        }
+        if let Some(size) = event
+            .size()
+            .filter(|_| event.event_type() == EventType::Resize)
+        {
+            resizes.push((num_events, size));
+        }
+        num_events += 1;
+        if !args.stream {
+            kept.push(event);
+        }
+    }
+    let events: Vec<asciicast::Event> = kept;
+    if num_events == 0 {
+        return Err(anyhow::anyhow!("The asciicast must have at least 1 event"));
+    }
+    let from: Option<f64> = position_time(args.from.as_ref(), &markers)?;
+    let to: Option<f64> = position_time(args.to.as_ref(), &markers)?;
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err(anyhow::anyhow!(
+                "--from at {}s is after --to at {}s",
+                from,
+                to
+            ));
+        }
+    }
+    if let Some(speed) = args.speed.filter(|&speed| !positive(speed)) {
+        return Err(anyhow::anyhow!("Speed must be positive, found {}", speed));
+    }
+    if let Some(duration) = args.duration.filter(|&duration| !positive(duration)) {
+        return Err(anyhow::anyhow!(
+            "Duration must be positive, found {}",
+            duration
+        ));
+    }
+    for (name, pause) in [("Start", args.start_pause), ("End", args.end_pause)] {
+        if !(pause.is_finite() && pause >= 0.0) {
+            return Err(anyhow::anyhow!(
+                "{} pause must be finite and not negative, found {}",
+                name,
+                pause
+            ));
+        }
+    }
+    let min_frame_interval: Option<f64> = match (args.fps, args.min_frame_interval) {
+        (Some(fps), _) if fps <= 0.0 => {
+            return Err(anyhow::anyhow!("FPS must be positive, found {}", fps));
+        }
+        (Some(fps), _) => Some(1.0 / fps),
+        (None, interval) => interval,
+    };
 
-    // create SVG symbols from the asciicast data
-    let symbol_map: BTreeMap<Symbol, Vec<usize>> = symbol_map(&header, &events);
-    let color_map: HashMap<(u8, u8, u8), (ColorAttribute, String)> = color_map(&symbol_map);
+    // keyframes are created as the event times are read, when streaming the
+    // times are read again
+    let times = || -> anyhow::Result<Times> {
+        if args.stream {
+            let (_, events) = open_asciicast(input)?;
+            let times = events.map(|event| event.map(|event| event.time()));
+            Ok(Times::new(times, idle_limit))
+        } else {
+            let times = events.iter().map(|event| Ok(event.time()));
+            Ok(Times::new(times, idle_limit))
+        }
+    };
+    let speed: Option<f64> = match (args.speed, args.duration) {
+        (Some(speed), _) => Some(speed),
+        (None, Some(duration)) => {
+            // the span of the keyframes is needed to scale them
+            let mut times: Times = times()?;
+            let mut keyframes = timing::keyframes(times.by_ref(), from, to);
+            let first: Option<Keyframe> = keyframes.next();
+            let last: Option<Keyframe> = keyframes.last().or(first);
+            times.finish()?;
+            first
+                .zip(last)
+                .and_then(|(first, last)| timing::speed_for(first.time, last.time, duration))
+        }
+        (None, None) => None,
+    };
+    let mut times: Times = times()?;
+    let mut selected = timing::keyframes(times.by_ref(), from, to).peekable();
+    if selected.peek().is_none() {
+        drop(selected);
+        times.finish()?;
+        return Err(anyhow::anyhow!(
+            "The asciicast has no events before {}s",
+            to.unwrap_or_default()
+        ));
+    }
+    let mut keyframes: Vec<Keyframe> = match still_frame(&args, &mut selected) {
+        Some(keyframe) => {
+            log::debug!("Rendering a still frame after event {}", keyframe.event);
+            vec![keyframe]
+        }
+        None => {
+            let scaled: Box<dyn Iterator<Item = Keyframe>> = match speed {
+                Some(speed) => Box::new(timing::scale(selected, speed)),
+                None => Box::new(selected),
+            };
+            timing::coalesce(
+                timing::start_pause(scaled, args.start_pause),
+                min_frame_interval,
+            )
+        }
+    };
+    times.finish()?;
+    let timeline: Timeline = Timeline::new(&keyframes, args.end_pause);
+    timeline.merge(&mut keyframes);
+
+    let pool: Option<ThreadPool> = if args.jobs == 1 {
+        None
+    } else {
+        let pool: ThreadPool = rayon::ThreadPoolBuilder::new()
+            .num_threads(args.jobs)
+            .build()
+            .context("Failed to create thread pool")?;
+        Some(pool)
+    };
+
+    // create SVG symbols from the asciicast data, in a viewport sized to the
+    // keyframes
+    let create_symbols = |keyframes: &mut Vec<Keyframe>| -> anyhow::Result<(Viewport, SymbolMap)> {
+        let (columns, rows) = largest_size(&header, &resizes, keyframes);
+        let viewport: Viewport = Viewport {
+            width: args.width.unwrap_or(columns),
+            height: args.height.unwrap_or(rows),
+            anchor: args.anchor,
+        };
+        if (columns, rows) != (header.width, header.height) {
+            log::debug!("Resizing the viewport to {}x{}", columns, rows);
+        }
+        let symbol_map: SymbolMap = if args.stream {
+            let (_, events) = open_asciicast(input)?;
+            let unique: SymbolMap = SymbolMap::streaming();
+            symbol_map(&header, &viewport, events, keyframes, pool.as_ref(), unique)?
+        } else {
+            let events = events.iter().cloned().map(Ok);
+            let unique: SymbolMap = SymbolMap::default();
+            symbol_map(&header, &viewport, events, keyframes, pool.as_ref(), unique)?
+        };
+        Ok((viewport, symbol_map))
+    };
+    let (mut viewport, mut symbol_map) = create_symbols(&mut keyframes)?;
+    if let Some(frame) = args.frame {
+        // frames are counted after coalescing and dropping identical frames,
+        // the symbols are created again for the still frame
+        if frame >= keyframes.len() {
+            return Err(anyhow::anyhow!(
+                "Frame {} is out of range, the animation has {} frames",
+                frame,
+                keyframes.len()
+            ));
+        }
+        log::debug!(
+            "Rendering a still frame after event {}",
+            keyframes[frame].event
+        );
+        keyframes = vec![keyframes[frame]];
+        (viewport, symbol_map) = create_symbols(&mut keyframes)?;
+    }
+    let num_frames: usize = symbol_map.num_frames;
+    debug_assert_eq!(num_frames, keyframes.len());
+    let still: bool = num_frames == 1;
+    let color_vars: Option<Vec<ColorKey>> = if args.adaptive {
+        Some(color_vars(&symbol_map))
+    } else {
+        None
+    };
+    let color_map: BTreeMap<ColorKey, (ColorAttribute, String)> =
+        color_map(&symbol_map, color_vars.as_deref());
+    let chars: BTreeSet<char> = symbol_map
+        .symbols()
+        .flat_map(|symbol| symbol.text.chars())
+        .collect();
+    let font_face: Option<String> = match args.embed_font.as_ref() {
+        Some(path) => Some(font::font_face(path, &chars)?),
+        None => None,
+    };
+    let glyphs: Option<Glyphs> = match args.text_to_paths.as_ref() {
+        Some(path) => Some(Glyphs::load(path, &chars)?),
+        None => None,
+    };
 
     // compose the SVG
     let opt = xmlwriter::Options {
@@ -278,27 +1111,39 @@ fn main() -> anyhow::Result<()> {
     };
     let mut svg = XmlWriter::new(opt);
 
-    let (width_pad, height_pad) = if args.window { (40, 60.0) } else { (0, 0.0) };
+    let layout: Layout = Layout {
+        font_size: args.font_size,
+        line_height: args.line_height,
+        column_width: args.column_width,
+    };
 
-    let header_height: usize = args.height.unwrap_or(header.height);
-    let header_width: usize = args.width.unwrap_or(header.width);
+    let (width_pad, height_pad) = if args.window {
+        (40.0, 60.0)
+    } else {
+        (0.0, 0.0)
+    };
 
-    let svg_height: f64 = (header_height as f64) * HEIGHT_SCALE + height_pad;
-    let svg_width: usize = header_width * WIDTH_SCALE + width_pad;
+    let svg_height: f64 = layout.height(viewport.height) + height_pad;
+    let svg_width: f64 = layout.width(viewport.width) + width_pad;
 
     svg.start_element("svg");
-    svg.write_attribute_fmt("height", format_args!("{:.2}", svg_height));
-    svg.write_attribute_fmt("width", format_args!("{:.2}", svg_width));
+    svg.write_attribute("height", &trimmed(svg_height, 2));
+    svg.write_attribute("width", &trimmed(svg_width, 2));
     svg.write_attribute("xmlns", "http://www.w3.org/2000/svg");
 
     svg.start_element("rect");
-    svg.write_attribute_fmt("height", format_args!("{:.2}", svg_height));
-    svg.write_attribute_fmt("width", format_args!("{:.2}", svg_width));
+    svg.write_attribute("height", &trimmed(svg_height, 2));
+    svg.write_attribute("width", &trimmed(svg_width, 2));
     if args.window {
         svg.write_attribute("rx", "5");
         svg.write_attribute("ry", "5");
     }
-    svg.write_attribute("style", "fill: #262626");
+    if args.adaptive {
+        svg.write_attribute("style", "fill: var(--bg)");
+    } else {
+        let (r, g, b) = DARK_BACKGROUND;
+        svg.write_attribute_fmt("style", format_args!("fill: #{:02x}{:02x}{:02x}", r, g, b));
+    }
     svg.end_element(); // rect
 
     if args.window {
@@ -327,15 +1172,15 @@ fn main() -> anyhow::Result<()> {
     }
 
     svg.start_element("svg");
-    svg.write_attribute_fmt("height", format_args!("{:.2}", svg_height));
-    svg.write_attribute_fmt("width", format_args!("{:.2}", svg_width));
+    svg.write_attribute("height", &trimmed(svg_height, 2));
+    svg.write_attribute("width", &trimmed(svg_width, 2));
     svg.write_attribute("xmlns", "http://www.w3.org/2000/svg");
     svg.write_attribute("xmlns:xlink", "http://www.w3.org/1999/xlink");
     if args.window {
         svg.write_attribute("x", "15");
         svg.write_attribute("y", "50");
     }
-    let viewbox_width: String = viewbox_dimension(svg_width as f64);
+    let viewbox_width: String = viewbox_dimension(svg_width);
     let viewbox_height: String = viewbox_dimension(svg_height);
     svg.write_attribute_fmt(
         "viewBox",
@@ -343,116 +1188,282 @@ fn main() -> anyhow::Result<()> {
     );
 
     svg.start_element("g");
-    svg.write_attribute("font-family", "Courier New");
-    svg.write_attribute_fmt("font-size", format_args!("{:.2}", FONT_SIZE));
+    if font_face.is_some() {
+        svg.write_attribute_fmt(
+            "font-family",
+            format_args!("{}, {}", font::EMBEDDED_FAMILY, args.font_family),
+        );
+    } else {
+        svg.write_attribute("font-family", &args.font_family);
+    }
+    svg.write_attribute_fmt("font-size", format_args!("{:.2}", layout.font_size / 10.0));
     svg.start_element("defs");
 
-    for (symbol_id, (symbol, frames)) in symbol_map.iter().enumerate() {
-        debug_assert!(!frames.is_empty());
-        if frames.len() > 1 {
+    if let Some(glyphs) = glyphs.as_ref() {
+        let scale: String = trimmed(layout.font_size / 10.0 / glyphs.units_per_em, 8);
+        for (idx, outline) in glyphs.outlines.iter().enumerate() {
+            svg.start_element("path");
+            svg.write_attribute_fmt("id", format_args!("g{}", idx));
+            svg.write_attribute_fmt("transform", format_args!("scale({},-{})", scale, scale));
+            svg.write_attribute("d", outline);
+            svg.end_element(); // path
+        }
+    }
+
+    // symbols are drawn once in delta layout
+    let delta: bool = !still && args.frame_layout == FrameLayout::Delta;
+    for (symbol_id, (symbol, num_frames)) in symbol_map.iter().enumerate() {
+        debug_assert!(*num_frames > 0);
+        if *num_frames > 1 && !delta {
             svg.start_element("symbol");
             svg.write_attribute_fmt("id", format_args!("{}", symbol_id));
-            write_text_element(&mut svg, &color_map, symbol);
+            write_text_element(&mut svg, &layout, &color_map, glyphs.as_ref(), symbol);
             svg.end_element(); // symbol
         }
     }
     svg.end_element(); // defs
-    svg.start_element("g");
-    svg.write_attribute_fmt(
-        "style",
-        format_args!(
-            "animation-duration:{}s;\
-            animation-iteration-count:infinite;\
-            animation-name:l;\
-            animation-timing-function:steps(1,end)",
-            duration
-        ),
-    );
-    svg.start_element("svg");
-    svg.write_attribute_fmt("width", format_args!("{}", num_events * svg_width));
 
-    for frame in 0..num_events {
-        svg.start_element("svg");
-        let offset: usize = frame * svg_width;
-        if frame != 0 {
-            svg.write_attribute_fmt("x", format_args!("{}", offset));
+    let write_symbols = |svg: &mut XmlWriter, ids: &[usize]| {
+        for &symbol_id in ids.iter() {
+            let (symbol, num_frames) = &symbol_map.symbols[symbol_id];
+            if *num_frames == 1 {
+                write_text_element(svg, &layout, &color_map, glyphs.as_ref(), symbol);
+            } else {
+                svg.start_element("use");
+                svg.write_attribute_fmt("xlink:href", format_args!("#{}", symbol_id));
+                svg.end_element(); // use
+            }
         }
+    };
 
-        for (symbol_id, (symbol, frames)) in symbol_map.iter().enumerate() {
-            if frames.contains(&frame) {
-                if frames.len() == 1 {
-                    write_text_element(&mut svg, &color_map, symbol);
-                } else {
-                    svg.start_element("use");
-                    svg.write_attribute_fmt("xlink:href", format_args!("#{}", symbol_id));
-                    svg.end_element(); // use
-                }
+    // symbols of a frame as an XML fragment
+    let serialize = |ids: &[usize]| -> String {
+        if ids.is_empty() {
+            String::new()
+        } else {
+            let mut fragment: XmlWriter = XmlWriter::new(opt);
+            write_symbols(&mut fragment, ids);
+            fragment.end_document()
+        }
+    };
+
+    // write a frame, wrapped in an element that positions it in the layout
+    let write_frame = |svg: &mut XmlWriter,
+                       animator: &mut Animator,
+                       frame: usize,
+                       content: &dyn Fn(&mut XmlWriter)| {
+        if still {
+            content(svg);
+        } else if args.frame_layout == FrameLayout::Stacked {
+            svg.start_element("g");
+            if frame != 0 {
+                svg.write_attribute("visibility", "hidden");
             }
+            animator.visibility(svg, &frame_intervals(&keyframes, &[frame]));
+            content(svg);
+            svg.end_element(); // g
+        } else {
+            svg.start_element("svg");
+            let offset: f64 = (frame as f64) * svg_width;
+            if frame != 0 {
+                svg.write_attribute("x", &trimmed(offset, 2));
+            }
+            content(svg);
+            svg.end_element(); // svg
         }
+    };
 
+    // frames are written as fragments later when streaming or in parallel,
+    // symbols are drawn once in delta layout
+    let fragments: bool = args.stream || (pool.is_some() && !delta);
+    let write_frames = |svg: &mut XmlWriter, animator: &mut Animator| {
+        if fragments {
+            svg.write_comment(stream::FRAMES);
+        } else {
+            for frame in 0..num_frames {
+                let ids: &[usize] = symbol_map.frame(frame);
+                write_frame(svg, animator, frame, &|svg| write_symbols(svg, ids));
+            }
+        }
+    };
+
+    // translation of the strip at the start of each frame after the first
+    let strip_steps = || {
+        keyframes
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(frame_num, keyframe)| (keyframe.time, -svg_width * (frame_num as f64)))
+    };
+
+    let mut animator: Animator = Animator::new(args.animation, timeline, args.loop_count);
+    // the CSS rules are written from the keyframes at the end when streaming
+    if args.stream {
+        animator.defer_css();
+    }
+
+    if still || args.frame_layout == FrameLayout::Stacked {
+        write_frames(&mut svg, &mut animator);
+    } else if delta {
+        // group symbols that are visible in the same frames
+        let symbol_frames: Vec<Vec<usize>> = symbol_map.symbol_frames();
+        let mut groups: BTreeMap<&[usize], Vec<&Symbol>> = BTreeMap::new();
+        for (symbol, frames) in symbol_map.symbols().zip(symbol_frames.iter()) {
+            groups.entry(frames).or_default().push(symbol);
+        }
+        for (frames, symbols) in groups.iter() {
+            svg.start_element("g");
+            if frames.len() < num_frames {
+                if frames[0] != 0 {
+                    svg.write_attribute("visibility", "hidden");
+                }
+                animator.visibility(&mut svg, &frame_intervals(&keyframes, frames));
+            }
+            for symbol in symbols.iter() {
+                write_text_element(&mut svg, &layout, &color_map, glyphs.as_ref(), symbol);
+            }
+            svg.end_element(); // g
+        }
+    } else {
+        svg.start_element("g");
+        animator.translate_x(&mut svg, strip_steps());
+        svg.start_element("svg");
+        svg.write_attribute("width", &trimmed((num_frames as f64) * svg_width, 2));
+        write_frames(&mut svg, &mut animator);
         svg.end_element(); // svg
+        svg.end_element(); // g
     }
 
     svg.end_element(); // g
-    svg.end_element(); // svg
-    svg.end_element(); // g
-    svg.start_element("style");
-    svg.write_text("@keyframes l{");
 
-    for (event_num, event) in events.iter().enumerate() {
-        if event_num != 0 {
-            let pct: f64 = ((event.time() - first_event_time) / duration) * 100.0;
-            // e.g. "0.62%{transform:translateX(-1280px)}"
-            svg.write_text_fmt(format_args!(
-                "{:.1}%{{transform:translateX(-{}px)}}",
-                pct,
-                svg_width * event_num,
-            ));
+    // the CSS rules are a placeholder when they are written later
+    let write_style = |svg: &mut XmlWriter, css: Option<&str>| {
+        svg.start_element("style");
+        if let Some(font_face) = font_face.as_ref() {
+            svg.write_text(font_face);
+        }
+        match css {
+            Some(css) => svg.write_text(css),
+            None => svg.write_comment(stream::CSS),
+        }
+
+        for (key, (attribute_name, attribute_value)) in color_map.iter() {
+            if *attribute_name == ColorAttribute::Class {
+                svg.write_text_fmt(format_args!(
+                    ".{}{{fill:{}}}",
+                    attribute_value,
+                    fill(*key, color_vars.as_deref())
+                ));
+            }
         }
-    }
-    svg.write_text("}");
 
-    for ((r, g, b), (attribute_name, attribute_value)) in color_map.iter() {
-        if *attribute_name == ColorAttribute::Class {
+        if let Some(color_vars) = color_vars.as_ref() {
+            let palette = |background: (u8, u8, u8), light: bool| {
+                let (r, g, b) = background;
+                let mut palette: String = format!(":root{{--bg:#{:02x}{:02x}{:02x}", r, g, b);
+                for (idx, (dark_rgb, light_rgb)) in color_vars.iter().enumerate() {
+                    let (r, g, b) = if light { *light_rgb } else { *dark_rgb };
+                    palette.push_str(&format!(";--c{}:#{:02x}{:02x}{:02x}", idx, r, g, b));
+                }
+                palette.push('}');
+                palette
+            };
+            svg.write_text(&palette(DARK_BACKGROUND, false));
             svg.write_text_fmt(format_args!(
-                ".{}{{fill:#{:02x}{:02x}{:02x}}}",
-                attribute_value, r, g, b
+                "@media (prefers-color-scheme:light){{{}}}",
+                palette(LIGHT_BACKGROUND, true)
             ));
         }
+        svg.end_element(); // style
+    };
+
+    // the style depends on the frames when they are written as fragments
+    if fragments {
+        svg.write_comment(stream::STYLE);
+    } else {
+        write_style(&mut svg, Some(animator.css()));
     }
-    svg.end_element(); // style
 
-    {
-        let mut output = match args.output {
-            Some(filepath) => {
-                if filepath.exists() {
-                    std::fs::remove_file(&filepath).with_context(|| {
-                        format!(
-                            "Failed to remove existing output file: {}",
-                            filepath.to_string_lossy()
-                        )
-                    })?;
+    let mut output: Output = Output::open(args.output.as_deref(), args.gzip)?;
+    if fragments {
+        let document: String = svg.end_document();
+        let (head, indentation, rest) = stream::split(&document, stream::FRAMES);
+        output.write_all(head.as_bytes())?;
+
+        let write_frame_fragment =
+            |output: &mut dyn Write, animator: &mut Animator, frame: usize, content: &str| {
+                if still {
+                    return stream::write_fragment(output, content, indentation);
                 }
-                Box::new(
-                    OpenOptions::new()
-                        .write(true)
-                        .create(true)
-                        .open(&filepath)
-                        .with_context(|| {
-                            format!(
-                                "Failed to open file for writing: {}",
-                                filepath.to_string_lossy()
-                            )
-                        })?,
-                ) as Box<dyn Write>
+                let mut wrapper: XmlWriter = XmlWriter::new(opt);
+                write_frame(&mut wrapper, animator, frame, &|svg| {
+                    if !content.is_empty() {
+                        svg.write_comment(stream::FRAMES);
+                    }
+                });
+                stream::write_wrapped(output, &wrapper.end_document(), content, indentation)
+            };
+        if args.stream {
+            let (_, events) = open_asciicast(input)?;
+            let mut frame: usize = 0;
+            let map = |symbols: Vec<Symbol>| serialize(&symbol_map.ids(&symbols));
+            replay::replay(
+                &header,
+                &viewport,
+                events,
+                &keyframes,
+                pool.as_ref(),
+                map,
+                |_, content| {
+                    write_frame_fragment(&mut output, &mut animator, frame, &content)?;
+                    frame += 1;
+                    Ok(())
+                },
+            )?;
+        } else {
+            let frames: Vec<usize> = (0..num_frames).collect();
+            let contents: Vec<String> = replay::map_ordered(pool.as_ref(), frames, |frame| {
+                serialize(symbol_map.frame(frame))
+            });
+            for (frame, content) in contents.iter().enumerate() {
+                write_frame_fragment(&mut output, &mut animator, frame, content)?;
             }
-            None => Box::new(std::io::stdout()),
-        };
+        }
 
+        let (middle, indentation, tail) = stream::split(rest, stream::STYLE);
+        output.write_all(middle.as_bytes())?;
+        let mut fragment: XmlWriter = XmlWriter::new(opt);
+        if args.stream {
+            // write the CSS rules a frame at a time instead of keeping them
+            write_style(&mut fragment, None);
+            let style: String = fragment.end_document();
+            let (head, inner, tail) = stream::split(&style, stream::CSS);
+            stream::write_fragment(&mut output, head, indentation)?;
+            let inner: String = format!("{}{}", indentation, inner);
+            stream::write_text(&mut output, &inner, |output| {
+                if still {
+                    Ok(())
+                } else if args.frame_layout == FrameLayout::Stacked {
+                    for frame in 0..num_frames {
+                        let intervals = frame_intervals(&keyframes, &[frame]);
+                        animator.write_visibility_css(output, frame, &intervals)?;
+                    }
+                    Ok(())
+                } else {
+                    animator.write_translate_x_css(output, strip_steps())
+                }
+            })?;
+            stream::write_fragment(&mut output, tail, indentation)?;
+        } else {
+            write_style(&mut fragment, Some(animator.css()));
+            stream::write_fragment(&mut output, &fragment.end_document(), indentation)?;
+        }
+        output.write_all(tail.as_bytes())?;
+    } else {
         output.write_all(&svg.end_document().into_bytes())?;
-        output.write_all(&[b'\n'])?;
-        output.flush()?;
     }
+    output.write_all(b"\n")?;
+    output.finish()?;
 
     Ok(())
 }
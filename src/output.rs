@@ -0,0 +1,123 @@
+//! SVG output, optionally gzip compressed.
+use anyhow::Context;
+use flate2::{write::GzEncoder, Compression};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// Writer that counts the bytes written to it.
+pub struct Counter<W: Write> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> Counter<W> {
+    fn new(inner: W) -> Counter<W> {
+        Counter { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for Counter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written: usize = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Output file, or stdout.
+pub enum Output {
+    Plain(Box<dyn Write>),
+    /// Gzip compressed, counting the uncompressed and the compressed bytes.
+    Gzip(Counter<GzEncoder<Counter<Box<dyn Write>>>>),
+}
+
+impl Output {
+    /// Open the output file, or stdout if there is no output file.
+    ///
+    /// The output is gzip compressed if `gzip` is set, or the file extension
+    /// is `svgz`.
+    pub fn open(path: Option<&Path>, gzip: bool) -> anyhow::Result<Output> {
+        let writer: Box<dyn Write> = match path {
+            Some(filepath) => {
+                if filepath.exists() {
+                    std::fs::remove_file(filepath).with_context(|| {
+                        format!(
+                            "Failed to remove existing output file: {}",
+                            filepath.to_string_lossy()
+                        )
+                    })?;
+                }
+                let file: File = OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(filepath)
+                    .with_context(|| {
+                        format!(
+                            "Failed to open file for writing: {}",
+                            filepath.to_string_lossy()
+                        )
+                    })?;
+                Box::new(BufWriter::new(file))
+            }
+            None => Box::new(BufWriter::new(std::io::stdout())),
+        };
+
+        let svgz: bool = path
+            .and_then(|path| path.extension())
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("svgz"));
+        if gzip || svgz {
+            let encoder = GzEncoder::new(Counter::new(writer), Compression::best());
+            Ok(Output::Gzip(Counter::new(encoder)))
+        } else {
+            Ok(Output::Plain(writer))
+        }
+    }
+
+    /// Flush the output, finishing compression.
+    ///
+    /// The uncompressed and compressed sizes are reported on stderr.
+    pub fn finish(self) -> anyhow::Result<()> {
+        match self {
+            Output::Plain(mut writer) => writer.flush()?,
+            Output::Gzip(counter) => {
+                let raw: u64 = counter.count;
+                let mut compressed = counter
+                    .inner
+                    .finish()
+                    .context("Failed to finish gzip compression")?;
+                compressed.flush()?;
+                eprintln!(
+                    "Compressed {} bytes to {} bytes ({:.1}%)",
+                    raw,
+                    compressed.count,
+                    100.0 * (compressed.count as f64) / (raw.max(1) as f64)
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Plain(writer) => writer.write(buf),
+            Output::Gzip(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Plain(writer) => writer.flush(),
+            Output::Gzip(writer) => writer.flush(),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Replay asciicast events to reconstruct the terminal frames.
+use crate::asciicast::{Event, EventType, Header};
+use crate::frame::{Frame, Snapshot, Symbol, Viewport};
+use crate::timing::Keyframe;
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use std::sync::mpsc;
+
+/// Number of frames that are symbolized together on the thread pool.
+const BATCH_SIZE: usize = 128;
+
+/// Reconstruct the terminal frame based on the byte data, calling `f` with
+/// the frame at each keyframe.
+///
+/// This uses alacritty's [vte] crate to reconstruct the frames.
+///
+/// [vte]: https://github.com/alacritty/vte
+fn emulate<I, F>(header: &Header, events: I, keyframes: &[Keyframe], mut f: F) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = anyhow::Result<Event>>,
+    F: FnMut(&Keyframe, &Frame) -> anyhow::Result<()>,
+{
+    let mut frame: Frame = Frame::new(header.width, header.height);
+    let mut parser: vte::Parser = vte::Parser::new();
+    let mut remaining = keyframes.iter().peekable();
+    for (event_num, event) in events.into_iter().enumerate() {
+        let event: Event = event?;
+        log::trace!("Event number {}: x={}, y={}", event_num, frame.x, frame.y);
+        match event.event_type() {
+            EventType::Input | EventType::Output => {
+                for byte in event.event_data().as_bytes() {
+                    parser.advance(&mut frame, *byte)
+                }
+            }
+            EventType::Resize => {
+                let (width, height) = event.size().ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Invalid size {:?} in resize event number {}",
+                        event.event_data(),
+                        event_num
+                    )
+                })?;
+                frame.resize(width, height);
+            }
+            EventType::Marker | EventType::Exit => (),
+        }
+
+        while let Some(keyframe) = remaining.next_if(|k| k.event == event_num) {
+            f(keyframe, &frame)?;
+        }
+        if remaining.peek().is_none() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Replay asciicast events, calling `f` in order with the symbols of each
+/// keyframe after they are passed through `map`.
+///
+/// With a thread pool the frames are reconstructed on another thread, and
+/// snapshots of the frames are passed through `symbols` and `map` on the pool.
+pub fn replay<I, M, T, F>(
+    header: &Header,
+    viewport: &Viewport,
+    events: I,
+    keyframes: &[Keyframe],
+    pool: Option<&ThreadPool>,
+    map: M,
+    mut f: F,
+) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = anyhow::Result<Event>>,
+    I::IntoIter: Send,
+    M: Fn(Vec<Symbol>) -> T + Sync,
+    T: Send,
+    F: FnMut(&Keyframe, T) -> anyhow::Result<()>,
+{
+    let pool: &ThreadPool = match pool {
+        Some(pool) => pool,
+        None => {
+            return emulate(header, events, keyframes, |keyframe, frame| {
+                f(keyframe, map(frame.symbols(viewport)))
+            });
+        }
+    };
+
+    let events = events.into_iter();
+    std::thread::scope(|scope| {
+        let (sender, receiver) = mpsc::sync_channel::<anyhow::Result<Vec<(Keyframe, Snapshot)>>>(2);
+        scope.spawn(move || {
+            let mut batch: Vec<(Keyframe, Snapshot)> = Vec::with_capacity(BATCH_SIZE);
+            let result = emulate(header, events, keyframes, |keyframe, frame| {
+                batch.push((*keyframe, frame.snapshot()));
+                if batch.len() == BATCH_SIZE {
+                    let full = std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE));
+                    // the receiver has stopped on an error
+                    sender
+                        .send(Ok(full))
+                        .map_err(|_| anyhow::anyhow!("Replay was stopped"))?;
+                }
+                Ok(())
+            });
+            // errors sending are ignored, the receiver has already stopped
+            let _ = match result {
+                Ok(()) => sender.send(Ok(batch)),
+                Err(e) => sender.send(Err(e)),
+            };
+        });
+
+        for batch in receiver {
+            let mapped: Vec<(Keyframe, T)> =
+                map_ordered(Some(pool), batch?, |(keyframe, snapshot)| {
+                    (keyframe, map(snapshot.symbols(viewport)))
+                });
+            for (keyframe, value) in mapped.into_iter() {
+                f(&keyframe, value)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Map items in order, in parallel when there is a thread pool.
+pub fn map_ordered<T, U, M>(pool: Option<&ThreadPool>, items: Vec<T>, map: M) -> Vec<U>
+where
+    T: Send,
+    U: Send,
+    M: Fn(T) -> U + Sync + Send,
+{
+    match pool {
+        Some(pool) => pool.install(|| items.into_par_iter().map(map).collect()),
+        None => items.into_iter().map(map).collect(),
+    }
+}
@@ -0,0 +1,134 @@
+//! Streaming output.
+//!
+//! [`XmlWriter`] builds the whole document in memory.
+//! To stream, the document is first written as a skeleton, with placeholder
+//! comments where the frames and the style go.
+//! The skeleton is split at the placeholders, and the frames and the style are
+//! written between the parts as separate XML fragments.
+//! The CSS rules of the animation are written into the style a piece at a
+//! time, at their own placeholder.
+//!
+//! [`XmlWriter`]: xmlwriter::XmlWriter
+use std::io::{self, Write};
+
+/// Placeholder for the frames.
+pub const FRAMES: &str = "cast2svg-frames";
+/// Placeholder for the `<style>` element.
+pub const STYLE: &str = "cast2svg-style";
+/// Placeholder for the CSS rules of the animation, in the `<style>` element.
+pub const CSS: &str = "cast2svg-css";
+
+/// Split a skeleton document at a placeholder comment.
+///
+/// Returns the document before the placeholder, the indentation of the
+/// placeholder, and the document after the placeholder.
+pub fn split<'a>(document: &'a str, placeholder: &str) -> (&'a str, &'a str, &'a str) {
+    let comment: String = format!("<!--{}-->", placeholder);
+    let pos: usize = document
+        .find(&comment)
+        .expect("placeholder is missing from the document");
+    let before: &str = &document[..pos];
+    let head: &str = before.trim_end_matches(' ');
+    let indentation: &str = &before[head.len()..];
+    let after: &str = &document[pos + comment.len()..];
+    // an indented placeholder is on its own line
+    let tail: &str = if indentation.is_empty() {
+        after
+    } else {
+        after.strip_prefix('\n').unwrap_or(after)
+    };
+    (head, indentation, tail)
+}
+
+/// Write an XML fragment at the indentation of a placeholder.
+pub fn write_fragment(output: &mut dyn Write, fragment: &str, indentation: &str) -> io::Result<()> {
+    if indentation.is_empty() {
+        output.write_all(fragment.as_bytes())
+    } else {
+        for line in fragment.lines() {
+            output.write_all(indentation.as_bytes())?;
+            output.write_all(line.as_bytes())?;
+            output.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// Write an XML fragment wrapped in another fragment, at the indentation of a
+/// placeholder.
+///
+/// The content replaces the frames placeholder in the wrapper.
+pub fn write_wrapped(
+    output: &mut dyn Write,
+    wrapper: &str,
+    content: &str,
+    indentation: &str,
+) -> io::Result<()> {
+    if content.is_empty() {
+        return write_fragment(output, wrapper, indentation);
+    }
+    let (head, inner, tail) = split(wrapper, FRAMES);
+    write_fragment(output, head, indentation)?;
+    write_fragment(output, content, &format!("{}{}", indentation, inner))?;
+    write_fragment(output, tail, indentation)
+}
+
+/// Write a text node at the indentation of a placeholder, with `text`
+/// writing its content in pieces.
+///
+/// The content is written as is, it must not need escaping.
+pub fn write_text<F>(output: &mut dyn Write, indentation: &str, text: F) -> io::Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> io::Result<()>,
+{
+    output.write_all(indentation.as_bytes())?;
+    text(output)?;
+    if !indentation.is_empty() {
+        output.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_indented() {
+        let document: &str = "<svg>\n    <g>\n        <!--cast2svg-frames-->\n    </g>\n</svg>\n";
+        let (head, indentation, tail) = split(document, FRAMES);
+        assert_eq!(head, "<svg>\n    <g>\n");
+        assert_eq!(indentation, "        ");
+        assert_eq!(tail, "    </g>\n</svg>\n");
+
+        let mut output: Vec<u8> = Vec::new();
+        write_fragment(&mut output, "<svg>\n    <text/>\n</svg>\n", indentation).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "        <svg>\n            <text/>\n        </svg>\n"
+        );
+    }
+
+    #[test]
+    fn wrapped() {
+        let mut output: Vec<u8> = Vec::new();
+        let wrapper: &str = "<svg x=\"80\">\n    <!--cast2svg-frames-->\n</svg>\n";
+        write_wrapped(&mut output, wrapper, "<text/>\n", "    ").unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "    <svg x=\"80\">\n        <text/>\n    </svg>\n"
+        );
+
+        let mut output: Vec<u8> = Vec::new();
+        write_wrapped(&mut output, "<svg x=\"80\"/>", "", "").unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "<svg x=\"80\"/>");
+    }
+
+    #[test]
+    fn split_compact() {
+        let (head, indentation, tail) = split("<svg><g><!--cast2svg-style--></g></svg>", STYLE);
+        assert_eq!(head, "<svg><g>");
+        assert_eq!(indentation, "");
+        assert_eq!(tail, "</g></svg>");
+    }
+}
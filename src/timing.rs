@@ -0,0 +1,543 @@
+//! Frame timing.
+use std::{fmt, str::FromStr};
+
+/// A frame of the animation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    /// Index of the last event shown in the frame.
+    pub event: usize,
+    /// Start time of the frame, in seconds.
+    pub time: f64,
+}
+
+/// Maximum number of decimal places in keyframe percentages.
+///
+/// Keyframes closer than this are merged.
+const MAX_PRECISION: usize = 4;
+
+/// Timeline of the animation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timeline {
+    /// Start time of the first keyframe, in seconds.
+    start: f64,
+    /// Duration of the animation, in seconds.
+    pub duration: f64,
+    /// Number of decimal places required to distinguish keyframe percentages.
+    pub precision: usize,
+}
+
+impl Timeline {
+    /// Create a timeline for keyframes, holding the last keyframe for
+    /// `end_pause` seconds.
+    pub fn new(keyframes: &[Keyframe], end_pause: f64) -> Timeline {
+        let start: f64 = keyframes.first().map(|k| k.time).unwrap_or_default();
+        let end: f64 = keyframes.last().map(|k| k.time).unwrap_or_default();
+        let duration: f64 = end - start + end_pause;
+
+        // Percentages that are at least 10^-precision apart cannot round
+        // to the same value.
+        let min_gap: f64 = keyframes
+            .windows(2)
+            .map(|w| w[1].time - w[0].time)
+            .filter(|&gap| gap > 0.0)
+            .fold(f64::INFINITY, f64::min);
+        let min_gap_pct: f64 = min_gap / duration * 100.0;
+        let precision: usize = if min_gap_pct.is_finite() {
+            (-min_gap_pct.log10())
+                .ceil()
+                .clamp(1.0, MAX_PRECISION as f64) as usize
+        } else {
+            1
+        };
+
+        Timeline {
+            start,
+            duration,
+            precision,
+        }
+    }
+
+    /// Percentage of the animation at `time`.
+    pub fn percent(&self, time: f64) -> f64 {
+        ((time - self.start) / self.duration) * 100.0
+    }
+
+    /// Percentage of the animation at `time`, rounded to the precision.
+    fn rounded(&self, time: f64) -> i64 {
+        (self.percent(time) * 10_f64.powi(self.precision as i32)).round() as i64
+    }
+
+    /// Remove keyframes that land on the same percentage as the following
+    /// keyframe, these would never be shown.
+    pub fn merge(&self, keyframes: &mut Vec<Keyframe>) {
+        let num_keyframes: usize = keyframes.len();
+        // Keep the time of the earlier keyframe, the difference is below the
+        // precision, and this keeps the first keyframe at 0%.
+        keyframes.dedup_by(|keyframe, last| {
+            let same: bool = self.rounded(last.time) == self.rounded(keyframe.time);
+            if same {
+                last.event = keyframe.event;
+            }
+            same
+        });
+        if keyframes.len() != num_keyframes {
+            log::debug!("Merged {} keyframes", num_keyframes - keyframes.len());
+        }
+    }
+}
+
+/// Keyframes for each event between `from` and `to`, created from the event
+/// times as they are read.
+///
+/// The first keyframe starts at `from`, and shows every event up to that
+/// time.
+/// There are no keyframes if there are no events before `to`.
+pub struct Keyframes<I> {
+    times: std::iter::Enumerate<I>,
+    from: Option<f64>,
+    to: f64,
+    /// Keyframe at `from`, showing the events up to `from`.
+    pending: Option<Keyframe>,
+    /// Keyframe to return after the pending keyframe.
+    queued: Option<Keyframe>,
+    /// Whether an event after `to` has been read.
+    done: bool,
+}
+
+impl<I: Iterator<Item = f64>> Iterator for Keyframes<I> {
+    type Item = Keyframe;
+
+    fn next(&mut self) -> Option<Keyframe> {
+        if let Some(keyframe) = self.queued.take() {
+            return Some(keyframe);
+        }
+        if self.done {
+            return None;
+        }
+        for (event, time) in self.times.by_ref() {
+            if time > self.to {
+                break;
+            }
+            match self.from {
+                Some(from) if time <= from => self.pending = Some(Keyframe { event, time: from }),
+                _ => {
+                    let keyframe: Keyframe = Keyframe { event, time };
+                    return match self.pending.take() {
+                        Some(pending) => {
+                            self.queued = Some(keyframe);
+                            Some(pending)
+                        }
+                        None => Some(keyframe),
+                    };
+                }
+            }
+        }
+        self.done = true;
+        self.pending.take()
+    }
+}
+
+/// Create a keyframe for each event between `from` and `to`.
+///
+/// `times` are the times of every event, in ascending order.
+pub fn keyframes<I>(times: I, from: Option<f64>, to: Option<f64>) -> Keyframes<I::IntoIter>
+where
+    I: IntoIterator<Item = f64>,
+{
+    Keyframes {
+        times: times.into_iter().enumerate(),
+        from,
+        to: to.unwrap_or(f64::INFINITY),
+        pending: None,
+        queued: None,
+        done: false,
+    }
+}
+
+/// Collect keyframes, coalescing keyframes that are less than `interval`
+/// seconds apart.
+///
+/// Each coalesced keyframe shows the state at its start time, the time of
+/// the first keyframe in the interval.
+/// Later keyframes in the interval are shown from the next coalesced
+/// keyframe, so nothing is shown before it happens.
+/// The last keyframe is always kept, so the animation still ends at the last
+/// event.
+pub fn coalesce<I>(keyframes: I, interval: Option<f64>) -> Vec<Keyframe>
+where
+    I: IntoIterator<Item = Keyframe>,
+{
+    let mut num_keyframes: usize = 0;
+    let mut coalesced: Vec<Keyframe> = Vec::new();
+    // last keyframe in the current interval, after its start
+    let mut pending: Option<Keyframe> = None;
+    for keyframe in keyframes {
+        num_keyframes += 1;
+        match (coalesced.last_mut(), interval) {
+            (Some(last), Some(interval)) if keyframe.time - last.time < interval => {
+                if keyframe.time == last.time {
+                    last.event = keyframe.event;
+                } else {
+                    pending = Some(keyframe);
+                }
+            }
+            _ => {
+                coalesced.push(keyframe);
+                pending = None;
+            }
+        }
+    }
+    coalesced.extend(pending);
+    if interval.is_some() {
+        log::debug!(
+            "Coalesced {} keyframes into {}",
+            num_keyframes,
+            coalesced.len()
+        );
+    }
+    coalesced
+}
+
+/// Hold the first keyframe for an additional `pause` seconds.
+pub fn start_pause<I>(keyframes: I, pause: f64) -> impl Iterator<Item = Keyframe>
+where
+    I: IntoIterator<Item = Keyframe>,
+{
+    keyframes
+        .into_iter()
+        .enumerate()
+        .map(move |(idx, mut keyframe)| {
+            if idx > 0 {
+                keyframe.time += pause;
+            }
+            keyframe
+        })
+}
+
+/// Number of times the animation plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Loop {
+    Infinite,
+    Count(u32),
+}
+
+impl FromStr for Loop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "infinite" => Ok(Loop::Infinite),
+            _ => match s.parse::<u32>() {
+                Ok(0) | Err(_) => Err(format!(
+                    "expected a positive integer or 'infinite', found '{}'",
+                    s
+                )),
+                Ok(count) => Ok(Loop::Count(count)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Loop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Loop::Infinite => write!(f, "infinite"),
+            Loop::Count(count) => write!(f, "{}", count),
+        }
+    }
+}
+
+/// Position in the asciicast, in seconds or at a marker.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Position {
+    Time(f64),
+    /// Label of a marker event.
+    Marker(String),
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<f64>() {
+            Ok(time) => Ok(Position::Time(time)),
+            Err(_) => Ok(Position::Marker(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Position::Time(time) => write!(f, "{}", time),
+            Position::Marker(label) => write!(f, "{}", label),
+        }
+    }
+}
+
+impl Position {
+    /// Time of the position.
+    ///
+    /// `markers` are the time and label of each marker.
+    /// Returns `None` if there is no marker with the label.
+    pub fn time(&self, markers: &[(f64, String)]) -> Option<f64> {
+        match self {
+            Position::Time(time) => Some(*time),
+            Position::Marker(label) => markers
+                .iter()
+                .find(|(_, marker)| marker == label)
+                .map(|(time, _)| *time),
+        }
+    }
+}
+
+/// Limit the idle time between events.
+///
+/// Gaps between consecutive times that are longer than the limit are
+/// shortened to the limit, all following times are shifted back by the same
+/// amount.
+#[derive(Debug, Clone, Copy)]
+pub struct IdleLimit {
+    limit: Option<f64>,
+    /// Idle time removed so far.
+    offset: f64,
+    /// Previous time, before limiting.
+    previous: Option<f64>,
+}
+
+impl IdleLimit {
+    /// Limit gaps to `limit` seconds, or not at all for `None`.
+    pub fn new(limit: Option<f64>) -> IdleLimit {
+        IdleLimit {
+            limit,
+            offset: 0.0,
+            previous: None,
+        }
+    }
+
+    /// Limited time of the next event.
+    pub fn time(&mut self, original: f64) -> f64 {
+        if let (Some(limit), Some(previous)) = (self.limit, self.previous) {
+            let gap: f64 = original - previous;
+            if gap > limit {
+                self.offset += gap - limit;
+            }
+        }
+        self.previous = Some(original);
+        original - self.offset
+    }
+}
+
+/// Change the playback speed.
+///
+/// Time between keyframes is divided by `speed`, relative to the first
+/// keyframe.
+pub fn scale<I>(keyframes: I, speed: f64) -> impl Iterator<Item = Keyframe>
+where
+    I: IntoIterator<Item = Keyframe>,
+{
+    let mut first: Option<f64> = None;
+    keyframes.into_iter().map(move |mut keyframe| {
+        let first: f64 = *first.get_or_insert(keyframe.time);
+        keyframe.time = first + (keyframe.time - first) / speed;
+        keyframe
+    })
+}
+
+/// Playback speed to play keyframes from `first` to `last` in exactly
+/// `duration` seconds.
+///
+/// Returns `None` if there is no time between the keyframes to scale.
+pub fn speed_for(first: f64, last: f64, duration: f64) -> Option<f64> {
+    if last > first {
+        Some((last - first) / duration)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn position() {
+        let markers: Vec<(f64, String)> = vec![(1.0, "intro".into()), (4.0, "end".into())];
+        let time = |s: &str| s.parse::<Position>().unwrap().time(&markers);
+        assert_eq!(time("2.5"), Some(2.5));
+        assert_eq!(time("end"), Some(4.0));
+        assert_eq!(time("intro"), Some(1.0));
+        assert_eq!(time("outro"), None);
+    }
+
+    fn limit_idle(times: &[f64], limit: f64) -> Vec<f64> {
+        let mut idle: IdleLimit = IdleLimit::new(Some(limit));
+        times.iter().map(|&time| idle.time(time)).collect()
+    }
+
+    #[test]
+    fn idle() {
+        let times: Vec<f64> = limit_idle(&[0.5, 1.0, 10.0, 10.5, 30.0], 2.0);
+        assert_eq!(times, vec![0.5, 1.0, 3.0, 3.5, 5.5]);
+    }
+
+    fn keyframes(times: &[f64], from: Option<f64>, to: Option<f64>) -> Vec<Keyframe> {
+        super::keyframes(times.iter().copied(), from, to).collect()
+    }
+
+    fn times(keyframes: &[Keyframe]) -> Vec<f64> {
+        keyframes.iter().map(|k| k.time).collect()
+    }
+
+    #[test]
+    fn trim() {
+        let keyframes: Vec<Keyframe> = keyframes(&[0.5, 1.0, 3.0, 4.0, 6.0], Some(2.0), Some(4.5));
+        assert_eq!(
+            keyframes,
+            vec![
+                Keyframe {
+                    event: 1,
+                    time: 2.0
+                },
+                Keyframe {
+                    event: 2,
+                    time: 3.0
+                },
+                Keyframe {
+                    event: 3,
+                    time: 4.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn trim_empty() {
+        assert!(keyframes(&[1.0, 2.0], None, Some(0.5)).is_empty());
+        assert_eq!(keyframes(&[1.0, 2.0], None, None).len(), 2);
+    }
+
+    #[test]
+    fn trim_stops_reading() {
+        let mut num_read: usize = 0;
+        let times = [1.0, 2.0, 3.0, 4.0]
+            .iter()
+            .copied()
+            .inspect(|_| num_read += 1);
+        assert_eq!(super::keyframes(times, None, Some(2.5)).count(), 2);
+        assert_eq!(num_read, 3);
+    }
+
+    #[test]
+    fn speed() {
+        let keyframes: Vec<Keyframe> =
+            scale(keyframes(&[1.0, 2.0, 5.0], None, None), 2.0).collect();
+        assert_eq!(times(&keyframes), vec![1.0, 1.5, 3.0]);
+    }
+
+    #[test]
+    fn duration() {
+        let speed: Option<f64> = speed_for(1.0, 5.0, 12.0);
+        assert_eq!(speed_for(1.0, 1.0, 12.0), None);
+        let keyframes: Vec<Keyframe> =
+            scale(keyframes(&[1.0, 2.0, 5.0], None, None), speed.unwrap()).collect();
+        assert_eq!(times(&keyframes), vec![1.0, 4.0, 13.0]);
+    }
+
+    #[test]
+    fn coalesce_interval() {
+        let keyframes: Vec<Keyframe> = coalesce(
+            keyframes(&[0.0, 0.0, 0.05, 0.09, 0.1, 0.5, 0.55, 2.0], None, None),
+            Some(0.1),
+        );
+        assert_eq!(
+            keyframes,
+            vec![
+                Keyframe {
+                    event: 1,
+                    time: 0.0
+                },
+                Keyframe {
+                    event: 4,
+                    time: 0.1
+                },
+                Keyframe {
+                    event: 5,
+                    time: 0.5
+                },
+                Keyframe {
+                    event: 7,
+                    time: 2.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn coalesce_keeps_last() {
+        let keyframes: Vec<Keyframe> =
+            coalesce(keyframes(&[0.0, 0.3, 1.0, 1.2], None, None), Some(0.5));
+        assert_eq!(times(&keyframes), vec![0.0, 1.0, 1.2]);
+        assert_eq!(keyframes.last().unwrap().event, 3);
+    }
+
+    #[test]
+    fn pause() {
+        let keyframes: Vec<Keyframe> =
+            start_pause(keyframes(&[1.0, 2.0, 5.0], None, None), 2.0).collect();
+        assert_eq!(times(&keyframes), vec![1.0, 4.0, 7.0]);
+    }
+
+    #[test]
+    fn loop_count() {
+        assert_eq!("infinite".parse(), Ok(Loop::Infinite));
+        assert_eq!("3".parse(), Ok(Loop::Count(3)));
+        assert!("0".parse::<Loop>().is_err());
+        assert!("forever".parse::<Loop>().is_err());
+        assert_eq!(Loop::Count(3).to_string(), "3");
+    }
+
+    #[test]
+    fn precision() {
+        let keyframes: Vec<Keyframe> = keyframes(&[0.0, 1.0, 4.0], None, None);
+        let timeline: Timeline = Timeline::new(&keyframes, 0.0);
+        assert_eq!(timeline.duration, 4.0);
+        assert_eq!(timeline.precision, 1);
+        assert_eq!(timeline.percent(1.0), 25.0);
+
+        let long: Vec<Keyframe> = self::keyframes(&[0.0, 0.001, 1000.0], None, None);
+        let timeline: Timeline = Timeline::new(&long, 0.0);
+        assert_eq!(timeline.precision, 4);
+    }
+
+    #[test]
+    fn merge() {
+        let mut keyframes: Vec<Keyframe> =
+            keyframes(&[0.0, 0.0, 1.0, 1.000001, 1.000002, 2000.0], None, None);
+        let timeline: Timeline = Timeline::new(&keyframes, 0.0);
+        timeline.merge(&mut keyframes);
+        assert_eq!(
+            keyframes,
+            vec![
+                Keyframe {
+                    event: 1,
+                    time: 0.0
+                },
+                Keyframe {
+                    event: 4,
+                    time: 1.0
+                },
+                Keyframe {
+                    event: 5,
+                    time: 2000.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn idle_no_gaps() {
+        let times: Vec<f64> = limit_idle(&[0.0, 1.0, 2.0], 2.0);
+        assert_eq!(times, vec![0.0, 1.0, 2.0]);
+    }
+}
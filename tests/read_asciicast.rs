@@ -1,6 +1,10 @@
 use assert_cmd::prelude::*;
 use predicates::prelude::*;
-use std::{path::PathBuf, process::Command};
+use std::{
+    fs::File,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
 
 fn test_file(name: &str) -> String {
     let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -34,12 +38,22 @@ fn no_header() {
 #[test]
 fn min_events() {
     let mut cmd: Command = base_cmd();
-    cmd.arg(test_file("min_events.cast"));
+    cmd.arg(test_file("no_events.cast"));
     cmd.assert().failure().stderr(predicate::str::contains(
-        "asciicast must have at least 2 events",
+        "asciicast must have at least 1 event",
     ));
 }
 
+#[test]
+fn single_event() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("min_events.cast"));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(r#"y="1.67">hello</text>"#))
+        .stdout(predicate::str::contains("@keyframes").not());
+}
+
 #[test]
 fn bad_event() {
     let mut cmd: Command = base_cmd();
@@ -57,3 +71,117 @@ fn time_travel() {
         "asciicast event on line 5 went backwards in time",
     ));
 }
+
+#[test]
+fn stdin() {
+    for args in [vec![], vec!["-"]] {
+        let cast = File::open(test_file("min_events.cast")).unwrap();
+        let mut cmd: Command = base_cmd();
+        cmd.args(&args).stdin(Stdio::from(cast));
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains(r#"y="1.67">hello</text>"#));
+    }
+}
+
+#[test]
+fn stdin_stream() {
+    let cast = File::open(test_file("min_events.cast")).unwrap();
+    let mut cmd: Command = base_cmd();
+    cmd.args(["-", "--stream"]).stdin(Stdio::from(cast));
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Streaming requires an input file"));
+}
+
+#[test]
+fn compressed() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"));
+    let expected = cmd.output().unwrap();
+    assert!(expected.status.success());
+
+    for name in ["hello.cast.gz", "hello.cast.zst"] {
+        let mut cmd: Command = base_cmd();
+        cmd.arg(test_file(name));
+        cmd.assert()
+            .success()
+            .stdout(predicate::eq(expected.stdout.as_slice()));
+
+        let cast = File::open(test_file(name)).unwrap();
+        let mut cmd: Command = base_cmd();
+        cmd.stdin(Stdio::from(cast));
+        cmd.assert()
+            .success()
+            .stdout(predicate::eq(expected.stdout.as_slice()));
+    }
+}
+
+#[test]
+fn compressed_bad_event() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("bad_event.cast.gz"));
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Failed to deserialize line 4 from asciicast",
+    ));
+}
+
+#[test]
+fn v1() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"));
+    let expected = cmd.output().unwrap();
+    assert!(expected.status.success());
+
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello_v1.cast"));
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq(expected.stdout.as_slice()));
+}
+
+#[test]
+fn time_travel_v1() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("time_travel_v1.cast"));
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "asciicast v1 output 2 went backwards in time",
+    ));
+}
+
+#[test]
+fn v3() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"));
+    let expected = cmd.output().unwrap();
+    assert!(expected.status.success());
+
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello_v3.cast"));
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq(expected.stdout.as_slice()));
+}
+
+#[test]
+fn time_travel_v3() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("time_travel_v3.cast"));
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "asciicast event on line 4 has a negative interval",
+    ));
+}
+
+#[test]
+fn bad_v1() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("bad_v1.cast"));
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Failed to deserialize asciicast v1",
+        ))
+        .stderr(predicate::str::contains(
+            "expected a tuple of size 2 at line 2",
+        ));
+}
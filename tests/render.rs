@@ -0,0 +1,635 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::{io::Read, path::PathBuf, process::Command};
+
+fn test_file(name: &str) -> String {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests");
+    path.push(name);
+    path.to_string_lossy().to_string()
+}
+
+fn base_cmd() -> Command {
+    Command::cargo_bin("cast2svg").unwrap()
+}
+
+#[test]
+fn adaptive() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast")).arg("--adaptive");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(r#"style="fill: var(--bg)""#))
+        .stdout(predicate::str::contains("fill: var(--c1)"))
+        .stdout(predicate::str::contains(
+            "@media (prefers-color-scheme:light){:root{--bg:#ffffff;--c0:#262626;",
+        ))
+        .stdout(predicate::str::contains("#262626").count(2));
+}
+
+#[test]
+fn adaptive_same_dark_rgb() {
+    // truecolor with the same dark value as the default color
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("same_dark_rgb.cast")).arg("--adaptive");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            ":root{--bg:#262626;--c0:#cbbfbf;--c1:#cbbfbf}",
+        ))
+        .stdout(predicate::str::contains(
+            "{:root{--bg:#ffffff;--c0:#7c7575;--c1:#262626}}",
+        ));
+}
+
+#[test]
+fn layout() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--font-family", "Hack, monospace"])
+        .args(["--font-size", "20"])
+        .args(["--line-height", "25"])
+        .args(["--column-width", "12"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            r#"<svg height="100" width="240" "#,
+        ))
+        .stdout(predicate::str::contains(r#"viewBox="0 0 24 10""#))
+        .stdout(predicate::str::contains(
+            r#"<g font-family="Hack, monospace" font-size="2.00">"#,
+        ))
+        .stdout(predicate::str::contains(r#"x="7.2" y="4.50">World!"#));
+}
+
+#[test]
+fn embed_font() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .arg("--embed-font")
+        .arg(test_file("demo.ttf"));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#"<g font-family="cast2svg, Courier New""#,
+        ))
+        .stdout(predicate::str::contains(
+            "<style>@font-face{font-family:cast2svg;src:url(data:font/ttf;base64,",
+        ));
+}
+
+#[test]
+fn embed_font_doesnt_exist() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--embed-font", "test/file/doesnt/exist.ttf"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Failed to read font from"));
+}
+
+#[test]
+fn text_to_paths() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .arg("--text-to-paths")
+        .arg(test_file("demo.ttf"));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#"<path id="g0" transform="scale(0.001667,-0.001667)" d="M100 0L100 700"#,
+        ))
+        .stdout(predicate::str::contains(
+            r##"<g class="a"><use xlink:href="#g0" y="1.67"/><use xlink:href="#g0" x="2" y="1.67"/>"##,
+        ))
+        .stdout(predicate::str::contains("<text").not());
+}
+
+#[test]
+fn still_at() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast")).args(["--at", "0.65"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(r#"y="1.67">$ ec</text>"#))
+        .stdout(predicate::str::contains("@keyframes").not())
+        .stdout(predicate::str::contains("translateX").not());
+}
+
+#[test]
+fn still_last() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast")).arg("--last");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(r#"x="6" y="3.84">World!</text>"#))
+        .stdout(predicate::str::contains("<use").not())
+        .stdout(predicate::str::contains("@keyframes").not());
+}
+
+#[test]
+fn still_frame_out_of_range() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast")).args(["--frame", "7"]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Frame 7 is out of range, the animation has 7 frames",
+    ));
+}
+
+#[test]
+fn still_frame_of_animation() {
+    // frames are counted after identical frames are dropped
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("identical.cast")).arg("--last");
+    let expected = cmd.output().unwrap();
+    assert!(expected.status.success());
+
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("identical.cast")).args(["--frame", "1"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq(expected.stdout.as_slice()));
+
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("identical.cast")).args(["--frame", "2"]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Frame 2 is out of range, the animation has 2 frames",
+    ));
+
+    // and after coalescing
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--fps", "1"])
+        .args(["--frame", "5"]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Frame 5 is out of range, the animation has 3 frames",
+    ));
+}
+
+#[test]
+fn idle_limit() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--idle-limit", "0.2"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("animation-duration:0.9s;"))
+        .stdout(predicate::str::contains(
+            "@keyframes l{0%{transform:translateX(0)}22.2%{transform:translateX(-200px)}",
+        ));
+}
+
+#[test]
+fn idle_limit_not_positive() {
+    for limit in ["-1", "0", "NaN"] {
+        let mut cmd: Command = base_cmd();
+        cmd.arg(test_file("hello.cast"))
+            .arg(format!("--idle-limit={}", limit));
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains(format!(
+                "Idle limit must be positive, found {}",
+                limit
+            )));
+    }
+}
+
+#[test]
+fn idle_limit_zero_in_header() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"));
+    let expected = cmd.output().unwrap();
+    assert!(expected.status.success());
+
+    // the limit in the header is ignored
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("zero_idle_limit.cast"));
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq(expected.stdout.as_slice()));
+}
+
+#[test]
+fn speed() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast")).args(["--speed", "2"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("animation-duration:0.7s;"))
+        .stdout(predicate::str::contains(
+            "@keyframes l{0%{transform:translateX(0)}28.6%{transform:translateX(-200px)}",
+        ));
+}
+
+#[test]
+fn duration() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast")).args(["--duration", "12"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("animation-duration:12s;"));
+}
+
+#[test]
+fn speed_and_duration_not_positive() {
+    for (option, value, message) in [
+        ("--speed", "NaN", "Speed must be positive, found NaN"),
+        ("--speed", "inf", "Speed must be positive, found inf"),
+        ("--speed", "0", "Speed must be positive, found 0"),
+        ("--duration", "NaN", "Duration must be positive, found NaN"),
+        ("--duration", "inf", "Duration must be positive, found inf"),
+    ] {
+        let mut cmd: Command = base_cmd();
+        cmd.arg(test_file("hello.cast")).args([option, value]);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains(message));
+    }
+}
+
+#[test]
+fn trim() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--from", "0.65"])
+        .args(["--to", "1.3"]);
+    cmd.assert()
+        .success()
+        // first frame shows the events before --from
+        .stdout(predicate::str::contains(
+            r#"<svg width="800"><svg><text class="a" y="1.67">$ ec</text></svg>"#,
+        ))
+        .stdout(predicate::str::contains("translateX(-600px)"))
+        .stdout(predicate::str::contains("translateX(-800px)").not());
+}
+
+#[test]
+fn trim_empty() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast")).args(["--to", "0.05"]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "The asciicast has no events before 0.05s",
+    ));
+}
+
+#[test]
+fn trim_reversed() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--from", "1"])
+        .args(["--to", "0.5"]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "--from at 1s is after --to at 0.5s",
+    ));
+}
+
+#[test]
+fn pause_and_loop() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--start-pause", "1"])
+        .args(["--end-pause", "2"])
+        .args(["--loop", "2"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "animation-iteration-count:2;animation-name:l;\
+            animation-timing-function:steps(1,end);animation-fill-mode:forwards",
+        ))
+        .stdout(predicate::str::contains(
+            "@keyframes l{0%{transform:translateX(0)}31.8%{transform:translateX(-200px)}",
+        ))
+        .stdout(predicate::str::contains(
+            "54.5%{transform:translateX(-1200px)}100%{transform:translateX(-1200px)}}",
+        ));
+}
+
+#[test]
+fn pause_not_finite() {
+    for (arg, found) in [
+        (
+            "--end-pause=NaN",
+            "End pause must be finite and not negative, found NaN",
+        ),
+        (
+            "--start-pause=inf",
+            "Start pause must be finite and not negative, found inf",
+        ),
+        (
+            "--start-pause=-1",
+            "Start pause must be finite and not negative, found -1",
+        ),
+    ] {
+        let mut cmd: Command = base_cmd();
+        cmd.arg(test_file("hello.cast")).arg(arg);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains(found));
+    }
+}
+
+#[test]
+fn keyframe_precision() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("merge.cast"));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "@keyframes l{0%{transform:translateX(0)}\
+            0.1%{transform:translateX(-200px)}\
+            100%{transform:translateX(-400px)}}",
+        ))
+        // the first three events are merged into a single frame
+        .stdout(predicate::str::contains(r#"<svg width="600"><svg><text"#))
+        .stdout(predicate::str::contains(r#">abc</text>"#));
+}
+
+#[test]
+fn min_frame_interval() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--min-frame-interval", "0.25"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "@keyframes l{0%{transform:translateX(0)}\
+            28.6%{transform:translateX(-200px)}\
+            50%{transform:translateX(-400px)}\
+            78.6%{transform:translateX(-600px)}\
+            100%{transform:translateX(-800px)}}",
+        ))
+        // each frame shows the state at its start, not events later in the
+        // interval
+        .stdout(predicate::str::contains(
+            r#"<svg x="200"><text class="a" y="1.67">$ e</text></svg>"#,
+        ));
+}
+
+#[test]
+fn fps() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast")).args(["--fps", "1"]);
+    cmd.assert()
+        .success()
+        // the last event still ends the animation
+        .stdout(predicate::str::contains("animation-duration:1.4s;"))
+        .stdout(predicate::str::contains(
+            "@keyframes l{0%{transform:translateX(0)}\
+            78.6%{transform:translateX(-200px)}\
+            100%{transform:translateX(-400px)}}",
+        ))
+        // "$ echo" is typed after the first frame starts
+        .stdout(predicate::str::contains(
+            r#"<svg width="600"><svg><text class="a" y="1.67">$ </text></svg>"#,
+        ));
+
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--duration", "2"])
+        .args(["--fps", "2"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("animation-duration:2s;"));
+}
+
+#[test]
+fn identical_frames() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("identical.cast"));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#"<svg width="400"><svg><text class="a" y="1.67">a</text></svg><svg x="200">"#,
+        ))
+        .stdout(predicate::str::contains(
+            "@keyframes l{0%{transform:translateX(0)}100%{transform:translateX(-200px)}}",
+        ));
+}
+
+#[test]
+fn smil() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--animation", "smil"])
+        .args(["--end-pause", "1"])
+        .args(["--loop", "3"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#"<g visibility="hidden"><animate attributeName="visibility" calcMode="discrete" dur="2.4s" repeatCount="3" fill="freeze" keyTimes="0;0.583;1" values="hidden;visible;visible"/>"#,
+        ))
+        .stdout(predicate::str::contains("animateTransform").not())
+        .stdout(predicate::str::contains("<svg width=").not())
+        .stdout(predicate::str::contains("@keyframes").not())
+        .stdout(predicate::str::contains("animation-duration").not());
+
+    // the strip layout is replaced with the stacked layout
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--animation", "smil"])
+        .args(["--frame-layout", "stacked"]);
+    let expected = cmd.output().unwrap();
+    assert!(expected.status.success());
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--animation", "smil"])
+        .args(["--frame-layout", "strip"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::eq(expected.stdout.as_slice()));
+}
+
+#[test]
+fn stacked() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--frame-layout", "stacked"])
+        .args(["--loop", "2"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "@keyframes v0{0%{visibility:visible}28.6%{visibility:hidden}100%{visibility:hidden}}",
+        ))
+        .stdout(predicate::str::contains(
+            "@keyframes v6{0%{visibility:hidden}100%{visibility:visible}}",
+        ))
+        .stdout(predicate::str::contains(r#"<g visibility="hidden" style="#))
+        .stdout(predicate::str::contains("translateX").not())
+        .stdout(predicate::str::contains("<svg width=").not());
+
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--frame-layout", "stacked"])
+        .args(["--animation", "smil"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#"<g visibility="hidden"><animate attributeName="visibility" calcMode="discrete" dur="1.4s" repeatCount="indefinite" keyTimes="0;0.286;0.357;1" values="hidden;visible;hidden;hidden"/>"#,
+        ));
+}
+
+#[test]
+fn delta() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--frame-layout", "delta"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "@keyframes v4{0%{visibility:hidden}50%{visibility:visible}100%{visibility:visible}}",
+        ))
+        .stdout(predicate::str::contains("<symbol").not())
+        .stdout(predicate::str::contains("<use").not())
+        .stdout(predicate::str::contains("@keyframes v7").not());
+}
+
+#[test]
+fn stream() {
+    for args in [
+        vec!["--indent"],
+        vec!["--adaptive"],
+        vec![
+            "--frame-layout",
+            "stacked",
+            "--animation",
+            "smil",
+            "--indent",
+        ],
+        vec!["--last"],
+        vec!["--frame-layout", "stacked"],
+        vec!["--frame-layout", "stacked", "--indent"],
+        vec!["--duration", "2", "--fps", "2"],
+    ] {
+        let mut cmd: Command = base_cmd();
+        cmd.arg(test_file("hello.cast")).args(&args);
+        let expected = cmd.output().unwrap();
+        assert!(expected.status.success());
+
+        let mut cmd: Command = base_cmd();
+        cmd.arg(test_file("hello.cast")).args(&args).arg("--stream");
+        cmd.assert()
+            .success()
+            .stdout(predicate::eq(expected.stdout.as_slice()));
+    }
+}
+
+#[test]
+fn stream_delta() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"))
+        .args(["--frame-layout", "delta"])
+        .arg("--stream");
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "The delta frame layout cannot be streamed",
+    ));
+}
+
+#[test]
+fn jobs() {
+    for args in [
+        vec!["--indent"],
+        vec!["--frame-layout", "stacked"],
+        vec!["--frame-layout", "delta"],
+        vec!["--stream", "--indent"],
+    ] {
+        let mut cmd: Command = base_cmd();
+        cmd.arg(test_file("hello.cast")).args(&args);
+        let expected = cmd.output().unwrap();
+        assert!(expected.status.success());
+
+        let mut cmd: Command = base_cmd();
+        cmd.arg(test_file("hello.cast"))
+            .args(&args)
+            .args(["--jobs", "3"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::eq(expected.stdout.as_slice()));
+    }
+}
+
+#[test]
+fn gzip() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast"));
+    let expected = cmd.output().unwrap();
+    assert!(expected.status.success());
+
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("hello.cast")).arg("--gzip");
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    let mut decompressed: Vec<u8> = Vec::new();
+    flate2::read::GzDecoder::new(output.stdout.as_slice())
+        .read_to_end(&mut decompressed)
+        .unwrap();
+    assert_eq!(decompressed, expected.stdout);
+    let stderr: String = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains(&format!(
+            "Compressed {} bytes to {} bytes",
+            expected.stdout.len(),
+            output.stdout.len()
+        )),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn trim_markers() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("markers.cast"))
+        .args(["--from", "typing"])
+        .args(["--to", "output"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#"<svg width="800"><svg><text class="a" y="1.67">$ e</text></svg>"#,
+        ))
+        .stdout(predicate::str::contains("Hello").not());
+
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("markers.cast")).args(["--from", "intro"]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "The asciicast has no marker labelled 'intro'",
+    ));
+}
+
+#[test]
+fn resize() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("markers.cast"));
+    cmd.assert()
+        .success()
+        // the resize to 4x2 keeps the rows at the cursor, clipped to 4 columns
+        .stdout(predicate::str::contains(
+            r#"<svg x="1400"><text class="b" y="1.67">Hell</text><text class="a" y="3.84">$ </text></svg>"#,
+        ));
+}
+
+#[test]
+fn resize_viewport() {
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("resize.cast"));
+    cmd.assert()
+        .success()
+        // sized to the largest frame, 20x4
+        .stdout(predicate::str::contains(r#"viewBox="0 0 20 8.684""#))
+        .stdout(predicate::str::contains(
+            r##"<svg><use xlink:href="#0"/></svg>"##,
+        ));
+
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("resize.cast"))
+        .args(["--anchor", "center"]);
+    cmd.assert().success().stdout(predicate::str::contains(
+        r#"<svg><text class="a" x="5" y="3.84">$ ls</text></svg>"#,
+    ));
+
+    let mut cmd: Command = base_cmd();
+    cmd.arg(test_file("resize.cast"))
+        .args(["--anchor", "bottom-left"]);
+    cmd.assert().success().stdout(predicate::str::contains(
+        r#"<svg><text class="a" y="6.01">$ ls</text></svg>"#,
+    ));
+}